@@ -0,0 +1,71 @@
+use bittorrent_rustico::peer::Bitfield;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const PIECES: usize = 100_000;
+
+// The bitfield as it was stored before, one byte per 8 pieces checked piece by piece
+struct ByteBitfield(Vec<u8>);
+
+impl ByteBitfield {
+    fn has_piece(&self, index: usize) -> bool {
+        let byte_index = index / 8;
+        let offset = index % 8;
+        if byte_index >= self.0.len() {
+            return false;
+        }
+        (self.0[byte_index] >> (7 - offset) & 1) != 0
+    }
+}
+
+fn random_payload(rng: &mut StdRng) -> Vec<u8> {
+    (0..PIECES.div_ceil(8)).map(|_| rng.gen()).collect()
+}
+
+fn bitfield_benchmark(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(42);
+    let peer_payload = random_payload(&mut rng);
+    let client_payload = random_payload(&mut rng);
+
+    let peer_bytes = ByteBitfield(peer_payload.clone());
+    let client_bytes = ByteBitfield(client_payload.clone());
+    let mut peer = Bitfield::new();
+    peer.set_bitfield(&peer_payload);
+    let mut client = Bitfield::new();
+    client.set_bitfield(&client_payload);
+
+    let mut group = c.benchmark_group("count_ones_100k_pieces");
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            (0..PIECES)
+                .filter(|index| black_box(&peer_bytes).has_piece(*index))
+                .count()
+        })
+    });
+    group.bench_function("u64_words", |b| b.iter(|| black_box(&peer).count_ones()));
+    group.finish();
+
+    let mut group = c.benchmark_group("needed_pieces_100k_pieces");
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            (0..PIECES)
+                .filter(|index| {
+                    black_box(&peer_bytes).has_piece(*index) && !client_bytes.has_piece(*index)
+                })
+                .collect::<Vec<usize>>()
+        })
+    });
+    group.bench_function("u64_words", |b| {
+        b.iter(|| {
+            black_box(&peer)
+                .and_not(&client)
+                .iter_ones()
+                .collect::<Vec<usize>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bitfield_benchmark);
+criterion_main!(benches);
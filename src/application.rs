@@ -1,17 +1,18 @@
 use crate::application_errors::ApplicationError;
 use crate::client::{ClientInfo, TorrentClient};
 use crate::constants::TIME_BETWEEN_ACCEPTS;
-use crate::download_manager::get_existing_pieces;
+use crate::download_manager::{restore_quarantined_pieces, verify_existing_pieces};
+use crate::event_bus::init_ui;
+use crate::event_bus::EventBusSender;
 use crate::server::Server;
-use crate::tracker::TrackerService;
-use crate::ui::{init_ui, UIMessage};
-use gtk::{self, glib};
+use crate::tracker::{AnnounceSchedulerSender, TrackerService};
 use log::*;
 
 pub fn run_with_torrent(
     torrent_path: &str,
     config_path: &str,
-    ui_message_sender: Option<glib::Sender<UIMessage>>,
+    ui_message_sender: Option<EventBusSender>,
+    announce_scheduler: AnnounceSchedulerSender,
 ) -> Result<(), ApplicationError> {
     let mut client_info = ClientInfo::new(torrent_path, config_path)?;
     let ui_message_sender = init_ui(ui_message_sender, &mut client_info);
@@ -21,7 +22,31 @@ pub fn run_with_torrent(
         client_info.config.download_path, client_info.metainfo.info.name
     );
 
-    let mut tracker_service = TrackerService::new(client_info.clone());
+    // pieces quarantined by a previous run are checked against their hash again, the valid ones
+    // are not downloaded again
+    let quarantine_dir = format!(
+        "{}/{}/quarantine",
+        client_info.config.download_path, client_info.metainfo.info.name
+    );
+    let still_quarantined = restore_quarantined_pieces(
+        &client_info.metainfo.info.pieces,
+        &quarantine_dir,
+        &pieces_dir,
+    );
+    if !still_quarantined.is_empty() {
+        warn!(
+            "Could not restore quarantined pieces: {:?}",
+            still_quarantined
+        );
+    }
+
+    // checked before the server starts, so corrupt pieces are never served
+    let initial_pieces: Vec<u32> =
+        verify_existing_pieces(&client_info.metainfo.info.pieces, pieces_dir.as_str());
+    debug!("Pieces already in {}: {:?}", pieces_dir, initial_pieces);
+
+    let mut tracker_service =
+        TrackerService::with_scheduler(client_info.clone(), announce_scheduler);
 
     let _ = Server::run(
         client_info.peer_id.to_vec(),
@@ -31,11 +56,6 @@ pub fn run_with_torrent(
         &pieces_dir,
         tracker_service.clone(),
     );
-    let initial_pieces: Vec<u32> =
-        get_existing_pieces(client_info.metainfo.get_piece_count(), pieces_dir.as_str());
-    println!("{}/pieces", client_info.config.download_path);
-    println!("i've got pieces: {:?}", initial_pieces);
-
     for _ in initial_pieces.clone() {
         ui_message_sender.send_downloaded_piece(client_info.peer_id.to_vec());
     }
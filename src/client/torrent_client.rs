@@ -1,13 +1,15 @@
 use super::ClientInfo;
 use crate::application_errors::ApplicationError;
 use crate::download_manager;
+use crate::event_bus::UIMessageSender;
+use crate::peer::Bitfield;
 use crate::peer_connection_manager::*;
 use crate::piece_manager::*;
 use crate::piece_saver::*;
 use crate::tracker::Event;
 use crate::tracker::ITrackerService;
-use crate::ui::UIMessageSender;
 use log::*;
+use std::sync::{Arc, RwLock};
 use std::thread::JoinHandle;
 
 pub struct ClientHandles {
@@ -39,6 +41,7 @@ impl TorrentClient {
     ) -> Result<Self, ApplicationError> {
         let (piece_manager_sender, piece_manager_worker) =
             Self::init_piece_manager(client_info, ui_message_sender.clone(), initial_pieces);
+        let have_pieces = piece_manager_worker.have_pieces.clone();
 
         let (piece_saver_sender, piece_saver_worker) = Self::init_piece_saver(
             piece_manager_sender.clone(),
@@ -51,6 +54,7 @@ impl TorrentClient {
                 piece_manager_sender,
                 piece_saver_sender,
                 client_info,
+                have_pieces,
                 ui_message_sender,
             );
 
@@ -188,6 +192,7 @@ impl TorrentClient {
         piece_manager_sender: PieceManagerSender,
         piece_saver_sender: PieceSaverSender,
         client_info: &ClientInfo,
+        have_pieces: Arc<RwLock<Bitfield>>,
         ui_message_sender: UIMessageSender,
     ) -> (PeerConnectionManagerSender, PeerConnectionManagerWorker) {
         new_peer_connection_manager(
@@ -195,6 +200,8 @@ impl TorrentClient {
             piece_saver_sender,
             &client_info.metainfo,
             &client_info.peer_id,
+            &client_info.config,
+            have_pieces,
             ui_message_sender,
         )
     }
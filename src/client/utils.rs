@@ -1,3 +1,4 @@
+use log::*;
 use rand::Rng;
 use sha1::{Digest, Sha1};
 
@@ -12,6 +13,6 @@ pub fn generate_peer_id_from_config_path(_config: &str) -> [u8; 20] {
     let peer_id = hasher.finalize().to_vec();
     let mut result = [0u8; 20];
     result[..20].clone_from_slice(&peer_id[..20]);
-    println!("{:?}", result);
+    debug!("Peer id: {:?}", result);
     result
 }
@@ -9,6 +9,8 @@ pub enum ConfigError {
     InvalidPath(String),
     /// there is a key missing in the config file
     MissingKey(String),
+    /// the value of an optional key is not valid
+    InvalidValue(String),
     CreateDirectoryError,
 }
 
@@ -27,6 +29,7 @@ impl std::fmt::Display for ConfigError {
                 write!(f, "{} is not an existing directory", e)
             }
             ConfigError::MissingKey(key) => write!(f, "Missing key: {}", key),
+            ConfigError::InvalidValue(key) => write!(f, "Invalid value for key: {}", key),
             ConfigError::CreateDirectoryError => {
                 write!(f, "Could not create download directory")
             }
@@ -1,15 +1,26 @@
 use super::errors::ConfigError;
 use crate::download_manager;
+use crate::peer::DialTimeouts;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path;
 use std::str;
+use std::time::Duration;
 const LISTEN_PORT: &str = "listen_port";
 const LOG_PATH: &str = "log_path";
 const DOWNLOAD_PATH: &str = "download_path";
 const SEPARATOR: &str = "=";
 const PERSIST_PIECES: &str = "persist_pieces";
+const CANDIDATE_TTL_SECS: &str = "candidate_ttl_secs";
+const CANDIDATE_MAX_FAILED_DIALS: &str = "candidate_max_failed_dials";
+const DEFAULT_CANDIDATE_TTL_SECS: u64 = 30 * 60;
+const DEFAULT_CANDIDATE_MAX_FAILED_DIALS: u32 = 3;
+const LAN_DIAL_TIMEOUT_SECS: &str = "lan_dial_timeout_secs";
+const WAN_DIAL_TIMEOUT_SECS: &str = "wan_dial_timeout_secs";
+const DEFAULT_LAN_DIAL_TIMEOUT_SECS: u64 = 2;
+const DEFAULT_WAN_DIAL_TIMEOUT_SECS: u64 = 100;
 use crate::logger::CustomLogger;
 
 const LOGGER: CustomLogger = CustomLogger::init("Config");
@@ -25,6 +36,12 @@ pub struct Config {
     pub download_path: String,
     /// whether to persist pieces in the disk or delete them after download
     pub persist_pieces: bool,
+    /// how long a peer received from the tracker can wait to be dialed before being dropped
+    pub candidate_ttl: Duration,
+    /// amount of failed dials after which a peer is no longer retried
+    pub candidate_max_failed_dials: u32,
+    /// connect timeouts for peers in the local network and on the internet
+    pub dial_timeouts: DialTimeouts,
 }
 
 impl Config {
@@ -56,11 +73,50 @@ impl Config {
         let config = create_config(&config_dictionary)?;
         Ok(config)
     }
+
+    /// settings that do not depend on the machine, as config file keys and values
+    pub fn portable_settings(&self) -> BTreeMap<String, String> {
+        [
+            (LISTEN_PORT, self.listen_port.to_string()),
+            (PERSIST_PIECES, self.persist_pieces.to_string()),
+            (CANDIDATE_TTL_SECS, self.candidate_ttl.as_secs().to_string()),
+            (
+                CANDIDATE_MAX_FAILED_DIALS,
+                self.candidate_max_failed_dials.to_string(),
+            ),
+            (
+                LAN_DIAL_TIMEOUT_SECS,
+                self.dial_timeouts.lan.as_secs().to_string(),
+            ),
+            (
+                WAN_DIAL_TIMEOUT_SECS,
+                self.dial_timeouts.wan.as_secs().to_string(),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+    }
+
+    /// contents of a config file with the given portable settings and the paths of this config
+    pub fn with_portable_settings(&self, settings: &BTreeMap<String, String>) -> String {
+        let mut lines: Vec<String> = settings
+            .iter()
+            .filter(|(key, _)| key.as_str() != DOWNLOAD_PATH && key.as_str() != LOG_PATH)
+            .map(|(key, value)| format!("{}{}{}", key, SEPARATOR, value))
+            .collect();
+        lines.push(format!(
+            "{}{}{}",
+            DOWNLOAD_PATH, SEPARATOR, self.download_path
+        ));
+        lines.push(format!("{}{}{}", LOG_PATH, SEPARATOR, self.log_path));
+        lines.join("\n") + "\n"
+    }
 }
 
 fn create_config(config_dict: &HashMap<String, String>) -> Result<Config, ConfigError> {
     let index = env::var("INDEX").unwrap_or_else(|_| "".to_string());
-    println!("index: {}", index);
+    LOGGER.debug(format!("index: {}", index));
     let listen_port: u16 = config_dict
         .get(LISTEN_PORT)
         .ok_or_else(|| ConfigError::MissingKey(LISTEN_PORT.to_string()))?
@@ -80,6 +136,25 @@ fn create_config(config_dict: &HashMap<String, String>) -> Result<Config, Config
         .get(PERSIST_PIECES)
         .ok_or_else(|| ConfigError::MissingKey(PERSIST_PIECES.to_string()))?;
 
+    let candidate_ttl_secs =
+        optional_number(config_dict, CANDIDATE_TTL_SECS, DEFAULT_CANDIDATE_TTL_SECS)?;
+    let candidate_max_failed_dials = optional_number(
+        config_dict,
+        CANDIDATE_MAX_FAILED_DIALS,
+        DEFAULT_CANDIDATE_MAX_FAILED_DIALS,
+    )?;
+
+    let lan_dial_timeout_secs = optional_timeout_secs(
+        config_dict,
+        LAN_DIAL_TIMEOUT_SECS,
+        DEFAULT_LAN_DIAL_TIMEOUT_SECS,
+    )?;
+    let wan_dial_timeout_secs = optional_timeout_secs(
+        config_dict,
+        WAN_DIAL_TIMEOUT_SECS,
+        DEFAULT_WAN_DIAL_TIMEOUT_SECS,
+    )?;
+
     download_manager::create_directory(&download_path)
         .map_err(|_| ConfigError::CreateDirectoryError)?;
 
@@ -93,9 +168,41 @@ fn create_config(config_dict: &HashMap<String, String>) -> Result<Config, Config
         log_path,
         download_path,
         persist_pieces: persist_pieces == "true",
+        candidate_ttl: Duration::from_secs(candidate_ttl_secs),
+        candidate_max_failed_dials,
+        dial_timeouts: DialTimeouts {
+            lan: Duration::from_secs(lan_dial_timeout_secs),
+            wan: Duration::from_secs(wan_dial_timeout_secs),
+        },
     })
 }
 
+// parses a key that can be left out of the config file
+fn optional_number<T: str::FromStr>(
+    config_dict: &HashMap<String, String>,
+    key: &str,
+    default: T,
+) -> Result<T, ConfigError> {
+    match config_dict.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue(key.to_string())),
+        None => Ok(default),
+    }
+}
+
+// parses a timeout that can be left out of the config file, connecting with a zero timeout fails
+fn optional_timeout_secs(
+    config_dict: &HashMap<String, String>,
+    key: &str,
+    default: u64,
+) -> Result<u64, ConfigError> {
+    match optional_number(config_dict, key, default)? {
+        0 => Err(ConfigError::InvalidValue(key.to_string())),
+        secs => Ok(secs),
+    }
+}
+
 //validates that path point to valid directories
 fn validate_path(path: &str) -> Result<(), ConfigError> {
     if !path::Path::new(path).exists() {
@@ -127,6 +234,54 @@ mod test {
         assert_eq!(config.log_path, "src/config/test_files/");
         assert_eq!(config.download_path, "src/config/test_files/");
         assert_eq!(config.persist_pieces, true);
+        assert_eq!(
+            config.candidate_ttl,
+            Duration::from_secs(DEFAULT_CANDIDATE_TTL_SECS)
+        );
+        assert_eq!(
+            config.candidate_max_failed_dials,
+            DEFAULT_CANDIDATE_MAX_FAILED_DIALS
+        );
+    }
+
+    #[test]
+    fn parses_candidate_pruning_config() {
+        let config =
+            Config::from_path("src/config/test_files/candidate_pruning_config.txt").unwrap();
+        assert_eq!(config.candidate_ttl, Duration::from_secs(120));
+        assert_eq!(config.candidate_max_failed_dials, 5);
+    }
+
+    #[test]
+    fn parses_dial_timeouts_config() {
+        let config = Config::from_path("src/config/test_files/dial_timeouts_config.txt").unwrap();
+        assert_eq!(config.dial_timeouts.lan, Duration::from_secs(1));
+        assert_eq!(config.dial_timeouts.wan, Duration::from_secs(15));
+
+        let config = Config::from_path("src/config/test_files/correct_config.txt").unwrap();
+        assert_eq!(
+            config.dial_timeouts.lan,
+            Duration::from_secs(DEFAULT_LAN_DIAL_TIMEOUT_SECS)
+        );
+    }
+
+    #[test]
+    fn throws_on_invalid_candidate_pruning_value() {
+        let config =
+            Config::from_path("src/config/test_files/invalid_candidate_pruning_config.txt");
+        assert_eq!(
+            config.unwrap_err(),
+            ConfigError::InvalidValue(CANDIDATE_MAX_FAILED_DIALS.to_string())
+        );
+    }
+
+    #[test]
+    fn throws_on_zero_dial_timeout() {
+        let config = Config::from_path("src/config/test_files/zero_dial_timeout_config.txt");
+        assert_eq!(
+            config.unwrap_err(),
+            ConfigError::InvalidValue(WAN_DIAL_TIMEOUT_SECS.to_string())
+        );
     }
 
     #[test]
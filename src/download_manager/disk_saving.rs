@@ -3,6 +3,7 @@ use super::types::Piece;
 use crate::logger::CustomLogger;
 use crate::server::client_has_piece;
 use log::*;
+use sha1::{Digest, Sha1};
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::copy;
@@ -24,7 +25,8 @@ pub fn create_directory(path: &str) -> Result<(), DownloadManagerError> {
 }
 
 /// Saves in disk a non-empty piece in the specified path with the number of piece as file name
-/// If the piece is empty, it returns an error
+/// If the piece is empty, it returns an error. The piece is written to a temporary file first,
+/// so a piece file is never left half written
 ///
 /// Returns a Result
 ///
@@ -59,12 +61,87 @@ pub fn save_piece_in_disk(
     }
     create_directory(downloads_dir_path)?;
 
-    let mut file = File::create(format!("{}/{}", downloads_dir_path, piece.piece_number))?;
+    let piece_path = format!("{}/{}", downloads_dir_path, piece.piece_number);
+    let temp_path = format!("{}.tmp", piece_path);
+    let mut file = File::create(&temp_path)?;
     file.write_all(&piece.data[..])?;
+    std::fs::rename(temp_path, piece_path)?;
 
     Ok(())
 }
 
+fn matches_hash(data: &[u8], expected_sha1: &[u8]) -> bool {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.finalize().as_slice() == expected_sha1
+}
+
+/// Whether the piece file at `piece_path` exists and matches `expected_sha1`
+pub fn piece_matches_hash(piece_path: &str, expected_sha1: &[u8]) -> bool {
+    match std::fs::read(piece_path) {
+        Ok(data) => matches_hash(&data, expected_sha1),
+        Err(_) => false,
+    }
+}
+
+/// Moves a piece from the quarantine dir, where it was spooled after failing to be saved,
+/// to the pieces dir. A piece that does not match `expected_sha1` is deleted instead
+pub fn restore_quarantined_piece(
+    piece_number: u32,
+    expected_sha1: &[u8],
+    quarantine_dir: &str,
+    pieces_dir: &str,
+) -> Result<(), DownloadManagerError> {
+    let quarantine_path = format!("{}/{}", quarantine_dir, piece_number);
+    let data = std::fs::read(&quarantine_path)?;
+    if !matches_hash(&data, expected_sha1) {
+        std::fs::remove_file(quarantine_path)?;
+        return Err(DownloadManagerError::CorruptPieceError(piece_number));
+    }
+    save_piece_in_disk(&Piece { piece_number, data }, pieces_dir)?;
+    std::fs::remove_file(quarantine_path)?;
+    Ok(())
+}
+
+/// Moves every piece left in the quarantine dir to the pieces dir, checking each one against
+/// its hash in `sha1_pieces`. Corrupt pieces are deleted, so they are downloaded again.
+/// Returns the pieces that are still quarantined
+pub fn restore_quarantined_pieces(
+    sha1_pieces: &[Vec<u8>],
+    quarantine_dir: &str,
+    pieces_dir: &str,
+) -> Vec<u32> {
+    let entries = match std::fs::read_dir(quarantine_dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|piece_number| {
+            let expected_sha1 = sha1_pieces
+                .get(*piece_number as usize)
+                .map(|sha1| sha1.as_slice())
+                .unwrap_or_default();
+            match restore_quarantined_piece(
+                *piece_number,
+                expected_sha1,
+                quarantine_dir,
+                pieces_dir,
+            ) {
+                Ok(()) => false,
+                Err(DownloadManagerError::CorruptPieceError(_)) => {
+                    LOGGER.error(format!(
+                        "Quarantined piece {} is corrupt, it will be downloaded again",
+                        piece_number
+                    ));
+                    false
+                }
+                Err(_) => true,
+            }
+        })
+        .collect()
+}
+
 pub fn join_all_pieces(
     piece_count: u32,
     target_file_name: &str,
@@ -125,9 +202,32 @@ pub fn get_existing_pieces(piece_count: u32, pieces_dir: &str) -> Vec<u32> {
     pieces
 }
 
+/// Checks every piece in the pieces dir against its hash in `sha1_pieces`, the pieces dir may
+/// have been copied from another machine. Corrupt pieces are deleted, so they are downloaded
+/// again instead of being served to other peers. Returns the pieces that are valid
+pub fn verify_existing_pieces(sha1_pieces: &[Vec<u8>], pieces_dir: &str) -> Vec<u32> {
+    get_existing_pieces(sha1_pieces.len() as u32, pieces_dir)
+        .into_iter()
+        .filter(|piece_number| {
+            let piece_path = format!("{}/{}", pieces_dir, piece_number);
+            if piece_matches_hash(&piece_path, &sha1_pieces[*piece_number as usize]) {
+                return true;
+            }
+            LOGGER.error(format!(
+                "Piece {} does not match its hash, it will be downloaded again",
+                piece_number
+            ));
+            let _ = std::fs::remove_file(piece_path);
+            false
+        })
+        .collect()
+}
+
+#[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    use crate::peer::sha1_of;
     #[allow(unused_imports)]
     use std::io::Read;
 
@@ -336,4 +436,74 @@ mod tests {
             Err(err) => assert!(matches!(err, DownloadManagerError::MissingPieceError(1))),
         }
     }
+
+    #[test]
+    fn restores_quarantined_pieces_into_pieces_dir() {
+        let test_dir = "./src/download_manager/test_downloads/quarantine";
+        let quarantine_dir = format!("{}/quarantine", test_dir);
+        let pieces_dir = format!("{}/pieces", test_dir);
+        let _ = std::fs::remove_dir_all(test_dir);
+        let sha1_pieces: Vec<Vec<u8>> = (0..6u8).map(|byte| sha1_of(&[byte; 10])).collect();
+        for piece_number in [2, 5] {
+            let piece = Piece {
+                piece_number,
+                data: vec![piece_number as u8; 10],
+            };
+            save_piece_in_disk(&piece, &quarantine_dir).unwrap();
+        }
+
+        let still_quarantined =
+            restore_quarantined_pieces(&sha1_pieces, &quarantine_dir, &pieces_dir);
+
+        assert!(still_quarantined.is_empty());
+        assert_eq!(
+            std::fs::read(format!("{}/5", pieces_dir)).unwrap(),
+            vec![5; 10]
+        );
+        assert!(!Path::new(&format!("{}/2", quarantine_dir)).exists());
+        std::fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn deletes_corrupt_quarantined_pieces() {
+        let test_dir = "./src/download_manager/test_downloads/corrupt_quarantine";
+        let quarantine_dir = format!("{}/quarantine", test_dir);
+        let pieces_dir = format!("{}/pieces", test_dir);
+        let _ = std::fs::remove_dir_all(test_dir);
+        let piece = Piece {
+            piece_number: 1,
+            data: vec![0; 10],
+        };
+        save_piece_in_disk(&piece, &quarantine_dir).unwrap();
+
+        let sha1_pieces = vec![sha1_of(&[0; 10]), sha1_of(&[1; 10])];
+        let still_quarantined =
+            restore_quarantined_pieces(&sha1_pieces, &quarantine_dir, &pieces_dir);
+
+        assert!(still_quarantined.is_empty());
+        assert!(!Path::new(&format!("{}/1", quarantine_dir)).exists());
+        assert!(!Path::new(&format!("{}/1", pieces_dir)).exists());
+        assert!(get_existing_pieces(2, &pieces_dir).is_empty());
+        std::fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn deletes_existing_pieces_that_do_not_match_their_hash() {
+        let pieces_dir = "./src/download_manager/test_downloads/verify";
+        let _ = std::fs::remove_dir_all(pieces_dir);
+        for piece_number in [0, 1] {
+            let piece = Piece {
+                piece_number,
+                data: vec![0; 10],
+            };
+            save_piece_in_disk(&piece, pieces_dir).unwrap();
+        }
+
+        let sha1_pieces = vec![sha1_of(&[0; 10]), sha1_of(&[1; 10]), sha1_of(&[2; 10])];
+        let verified_pieces = verify_existing_pieces(&sha1_pieces, pieces_dir);
+
+        assert_eq!(verified_pieces, vec![0]);
+        assert!(!Path::new(&format!("{}/1", pieces_dir)).exists());
+        std::fs::remove_dir_all(pieces_dir).unwrap();
+    }
 }
@@ -7,6 +7,7 @@ pub enum DownloadManagerError {
     CreateDirectoryError(String),
     CreateFileError(String),
     MissingPieceError(u32),
+    CorruptPieceError(u32),
 }
 
 impl From<io::Error> for DownloadManagerError {
@@ -31,6 +32,9 @@ impl std::fmt::Display for DownloadManagerError {
             DownloadManagerError::MissingPieceError(piece_no) => {
                 write!(f, "File for piece {} does not exist", piece_no)
             }
+            DownloadManagerError::CorruptPieceError(piece_no) => {
+                write!(f, "File for piece {} does not match its hash", piece_no)
+            }
         }
     }
 }
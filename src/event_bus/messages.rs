@@ -1,7 +1,7 @@
+use crate::client::ClientInfo;
+use crate::event_bus::{EventBusSender, UISnapshot};
 use crate::metainfo::Metainfo;
 use crate::peer::PeerConnectionState;
-use gtk::{self, glib};
-use log::*;
 
 type TorrentName = String;
 
@@ -16,6 +16,7 @@ pub struct PeerStatistics {
     pub uploadrate: u32,
 }
 
+#[derive(Clone)]
 pub enum UIMessage {
     AddTorrent(Metainfo),
     TorrentInitialPeers(TorrentName, u32),
@@ -23,15 +24,16 @@ pub enum UIMessage {
     NewConnection(TorrentName),
     ClosedConnection(TorrentName, Vec<u8>),
     AddPeerStatistics(PeerStatistics),
-    UpdatePeerUploadRate(f32, Vec<u8>),
-    UpdatePeerDownloadRate(f32, Vec<u8>),
-    UpdateDownloadedPiece(Vec<u8>),
-    UpdatePeerConnectionState(Vec<u8>, PeerConnectionState),
+    UpdatePeerUploadRate(TorrentName, f32, Vec<u8>),
+    UpdatePeerDownloadRate(TorrentName, f32, Vec<u8>),
+    UpdateDownloadedPiece(TorrentName, Vec<u8>),
+    UpdatePeerConnectionState(TorrentName, Vec<u8>, PeerConnectionState),
+    Snapshot(UISnapshot),
 }
 
 #[derive(Debug, Clone)]
 pub struct UIMessageSender {
-    pub tx: Option<glib::Sender<UIMessage>>,
+    pub tx: Option<EventBusSender>,
     torrent_name: String,
 }
 
@@ -43,7 +45,7 @@ impl UIMessageSender {
         }
     }
 
-    pub fn with_ui(torrent_name: &str, tx: glib::Sender<UIMessage>) -> Self {
+    pub fn with_ui(torrent_name: &str, tx: EventBusSender) -> Self {
         UIMessageSender {
             tx: Some(tx),
             torrent_name: torrent_name.to_string(),
@@ -84,21 +86,45 @@ impl UIMessageSender {
     }
 
     pub fn update_peer_state(&self, peer_id: Vec<u8>, state: PeerConnectionState) {
-        self.send_message_to_ui(UIMessage::UpdatePeerConnectionState(peer_id, state))
+        self.send_message_to_ui(UIMessage::UpdatePeerConnectionState(
+            self.torrent_name.clone(),
+            peer_id,
+            state,
+        ))
     }
 
     pub fn send_upload_rate(&self, rate: f32, peer_id: &[u8]) {
-        self.send_message_to_ui(UIMessage::UpdatePeerUploadRate(rate, peer_id.to_vec()))
+        self.send_message_to_ui(UIMessage::UpdatePeerUploadRate(
+            self.torrent_name.clone(),
+            rate,
+            peer_id.to_vec(),
+        ))
     }
     pub fn send_download_rate(&self, rate: f32, peer_id: &[u8]) {
-        self.send_message_to_ui(UIMessage::UpdatePeerDownloadRate(rate, peer_id.to_vec()))
+        self.send_message_to_ui(UIMessage::UpdatePeerDownloadRate(
+            self.torrent_name.clone(),
+            rate,
+            peer_id.to_vec(),
+        ))
     }
 
     pub fn send_message_to_ui(&self, message: UIMessage) {
         if let Some(tx) = &self.tx {
-            if tx.send(message).is_err() {
-                error!("Failed to send message to UI");
-            }
+            tx.publish(message);
         }
     }
 }
+
+/// Creates the sender a torrent publishes its events with, and announces the torrent. Without
+/// an event bus every event is dropped
+pub fn init_ui(
+    ui_message_sender: Option<EventBusSender>,
+    client_info: &mut ClientInfo,
+) -> UIMessageSender {
+    let ui_message_sender = match ui_message_sender {
+        Some(sender) => UIMessageSender::with_ui(&client_info.metainfo.info.name, sender),
+        None => UIMessageSender::no_ui(),
+    };
+    ui_message_sender.send_metadata(client_info.metainfo.clone());
+    ui_message_sender
+}
@@ -0,0 +1,11 @@
+mod messages;
+pub mod sender;
+mod snapshot;
+pub mod types;
+pub mod worker;
+
+pub use messages::{init_ui, PeerStatistics, UIMessage, UIMessageSender};
+pub use sender::EventBusSender;
+pub use snapshot::*;
+pub use types::*;
+pub use worker::EventBusWorker;
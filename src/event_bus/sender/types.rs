@@ -0,0 +1,34 @@
+use crate::event_bus::types::{EventBusMessage, IUIMessageSubscriber};
+use crate::event_bus::UIMessage;
+use crate::event_bus::UISnapshot;
+use log::*;
+use std::sync::mpsc::{self, Sender};
+
+#[derive(Clone, Debug)]
+pub struct EventBusSender {
+    pub sender: Sender<EventBusMessage>,
+}
+
+impl EventBusSender {
+    pub fn publish(&self, message: UIMessage) {
+        if self.sender.send(EventBusMessage::Publish(message)).is_err() {
+            error!("Failed to send message to UI");
+        }
+    }
+
+    pub fn subscribe(&self, subscriber: impl IUIMessageSubscriber + 'static) {
+        let _ = self
+            .sender
+            .send(EventBusMessage::Subscribe(Box::new(subscriber)));
+    }
+
+    /// Returns the complete state of every torrent known by the bus,
+    /// or None if the bus is no longer running
+    pub fn request_snapshot(&self) -> Option<UISnapshot> {
+        let (tx, rx) = mpsc::channel();
+        self.sender
+            .send(EventBusMessage::RequestSnapshot(tx))
+            .ok()?;
+        rx.recv().ok()
+    }
+}
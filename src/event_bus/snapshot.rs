@@ -0,0 +1,201 @@
+use crate::event_bus::{PeerStatistics, UIMessage};
+use crate::metainfo::Metainfo;
+
+/// State of a torrent as seen by a frontend that received every message since start up
+#[derive(Clone)]
+pub struct TorrentSnapshot {
+    pub metainfo: Metainfo,
+    pub peer_count: u32,
+    pub downloaded_pieces: u32,
+    pub active_connections: u32,
+}
+
+/// State of a connection with a peer, including the rates reported so far
+#[derive(Clone)]
+pub struct PeerSnapshot {
+    pub statistics: PeerStatistics,
+    pub downloaded_pieces: u32,
+    pub download_rate: f32,
+    pub upload_rate: f32,
+    pub is_connected: bool,
+}
+
+/// Complete torrent, peer and piece state, used to rebuild a frontend
+/// from scratch instead of replaying every incremental message
+#[derive(Clone, Default)]
+pub struct UISnapshot {
+    pub torrents: Vec<TorrentSnapshot>,
+    pub peers: Vec<PeerSnapshot>,
+}
+
+impl UISnapshot {
+    fn edit_torrent(&mut self, torrent_name: &str, f: impl Fn(&mut TorrentSnapshot)) {
+        self.torrents
+            .iter_mut()
+            .filter(|torrent| torrent.metainfo.info.name == torrent_name)
+            .for_each(f);
+    }
+
+    // a peer can serve several torrents, so it is identified by the torrent and its id
+    fn edit_peer(&mut self, torrent_name: &str, peer_id: &[u8], f: impl Fn(&mut PeerSnapshot)) {
+        self.peers
+            .iter_mut()
+            .filter(|peer| {
+                peer.statistics.torrentname == torrent_name && peer.statistics.peerid == peer_id
+            })
+            .for_each(f);
+    }
+
+    // a peer that reconnects to the same torrent replaces its previous entry
+    fn add_peer(&mut self, statistics: &PeerStatistics) {
+        let peer = PeerSnapshot {
+            statistics: statistics.clone(),
+            downloaded_pieces: 0,
+            download_rate: 0f32,
+            upload_rate: 0f32,
+            is_connected: true,
+        };
+        match self.peers.iter_mut().find(|known| {
+            known.statistics.torrentname == statistics.torrentname
+                && known.statistics.peerid == statistics.peerid
+        }) {
+            Some(known) => *known = peer,
+            None => self.peers.push(peer),
+        }
+    }
+
+    /// Updates the snapshot with a message, the same way the UI updates its models
+    pub fn apply(&mut self, message: &UIMessage) {
+        match message {
+            UIMessage::AddTorrent(metainfo) => self.torrents.push(TorrentSnapshot {
+                metainfo: metainfo.clone(),
+                peer_count: 0,
+                downloaded_pieces: 0,
+                active_connections: 0,
+            }),
+            UIMessage::TorrentInitialPeers(torrent, amount) => {
+                self.edit_torrent(torrent, |t| t.peer_count = *amount)
+            }
+            UIMessage::PieceDownloaded(torrent, peer_id) => {
+                self.edit_torrent(torrent, |t| t.downloaded_pieces += 1);
+                self.edit_peer(torrent, peer_id, |p| p.downloaded_pieces += 1);
+            }
+            UIMessage::NewConnection(torrent) => {
+                self.edit_torrent(torrent, |t| t.active_connections += 1)
+            }
+            UIMessage::ClosedConnection(torrent, peer_id) => {
+                self.edit_torrent(torrent, |t| {
+                    t.active_connections = t.active_connections.saturating_sub(1)
+                });
+                self.edit_peer(torrent, peer_id, |p| {
+                    p.is_connected = false;
+                    p.download_rate = 0f32;
+                });
+            }
+            UIMessage::AddPeerStatistics(statistics) => self.add_peer(statistics),
+            UIMessage::UpdatePeerUploadRate(torrent, rate, peer_id) => {
+                self.edit_peer(torrent, peer_id, |p| p.upload_rate = *rate)
+            }
+            UIMessage::UpdatePeerDownloadRate(torrent, rate, peer_id) => {
+                self.edit_peer(torrent, peer_id, |p| p.download_rate = *rate)
+            }
+            UIMessage::UpdateDownloadedPiece(torrent, peer_id) => {
+                self.edit_peer(torrent, peer_id, |p| p.downloaded_pieces += 1)
+            }
+            UIMessage::UpdatePeerConnectionState(torrent, peer_id, state) => {
+                self.edit_peer(torrent, peer_id, |p| p.statistics.state = state.clone())
+            }
+            UIMessage::Snapshot(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metainfo::Info;
+    use crate::peer::{PeerConnectionState, PeerState};
+
+    fn peer_statistics(peer_id: Vec<u8>) -> PeerStatistics {
+        PeerStatistics {
+            torrentname: "debian".to_string(),
+            peerid: peer_id,
+            ip: "127.0.0.1".to_string(),
+            port: 6881,
+            state: PeerConnectionState {
+                client: PeerState {
+                    chocked: true,
+                    interested: true,
+                },
+                peer: PeerState {
+                    chocked: true,
+                    interested: false,
+                },
+            },
+            downloadrate: 0,
+            uploadrate: 0,
+        }
+    }
+
+    #[test]
+    fn snapshot_accumulates_torrent_and_peer_state() {
+        let mut snapshot = UISnapshot::default();
+        let torrent = "debian".to_string();
+        snapshot.apply(&UIMessage::AddTorrent(Metainfo {
+            announce: "".to_string(),
+            announce_list: vec![],
+            info: Info {
+                piece_length: 8,
+                pieces: vec![vec![0; 20]; 3],
+                length: 24,
+                name: torrent.clone(),
+                files: None,
+            },
+            info_hash: vec![],
+        }));
+        snapshot.apply(&UIMessage::TorrentInitialPeers(torrent.clone(), 2));
+        snapshot.apply(&UIMessage::NewConnection(torrent.clone()));
+        snapshot.apply(&UIMessage::NewConnection(torrent.clone()));
+        snapshot.apply(&UIMessage::AddPeerStatistics(peer_statistics(vec![1])));
+        snapshot.apply(&UIMessage::AddPeerStatistics(peer_statistics(vec![2])));
+        snapshot.apply(&UIMessage::PieceDownloaded(torrent.clone(), vec![1]));
+        snapshot.apply(&UIMessage::UpdatePeerDownloadRate(
+            torrent.clone(),
+            10f32,
+            vec![1],
+        ));
+        snapshot.apply(&UIMessage::ClosedConnection(torrent, vec![2]));
+
+        assert_eq!(snapshot.torrents.len(), 1);
+        assert_eq!(snapshot.torrents[0].peer_count, 2);
+        assert_eq!(snapshot.torrents[0].downloaded_pieces, 1);
+        assert_eq!(snapshot.torrents[0].active_connections, 1);
+
+        assert_eq!(snapshot.peers.len(), 2);
+        assert_eq!(snapshot.peers[0].downloaded_pieces, 1);
+        assert_eq!(snapshot.peers[0].download_rate, 10f32);
+        assert!(snapshot.peers[0].is_connected);
+        assert!(!snapshot.peers[1].is_connected);
+    }
+
+    #[test]
+    fn snapshot_keeps_a_peer_apart_for_each_torrent() {
+        let mut snapshot = UISnapshot::default();
+        let mut ubuntu_statistics = peer_statistics(vec![1]);
+        ubuntu_statistics.torrentname = "ubuntu".to_string();
+        snapshot.apply(&UIMessage::AddPeerStatistics(peer_statistics(vec![1])));
+        snapshot.apply(&UIMessage::AddPeerStatistics(ubuntu_statistics));
+        snapshot.apply(&UIMessage::UpdatePeerDownloadRate(
+            "ubuntu".to_string(),
+            10f32,
+            vec![1],
+        ));
+        snapshot.apply(&UIMessage::ClosedConnection("debian".to_string(), vec![1]));
+
+        assert_eq!(snapshot.peers.len(), 2);
+        assert_eq!(snapshot.peers[0].download_rate, 0f32);
+        assert!(!snapshot.peers[0].is_connected);
+        assert_eq!(snapshot.peers[1].download_rate, 10f32);
+        assert!(snapshot.peers[1].is_connected);
+    }
+}
@@ -0,0 +1,38 @@
+use super::sender::types::EventBusSender;
+use super::snapshot::UISnapshot;
+use super::worker::types::EventBusWorker;
+use crate::event_bus::UIMessage;
+use std::sync::mpsc;
+
+/// A frontend fed by the event bus (the GTK window, a terminal UI, a test...)
+pub trait IUIMessageSubscriber: Send {
+    /// Delivers a message to the frontend, returns false once the frontend is gone
+    fn notify(&self, message: UIMessage) -> bool;
+}
+
+impl IUIMessageSubscriber for mpsc::Sender<UIMessage> {
+    fn notify(&self, message: UIMessage) -> bool {
+        self.send(message).is_ok()
+    }
+}
+
+pub enum EventBusMessage {
+    //Event produced by the client, recorded in the snapshot and forwarded to every subscriber
+    Publish(UIMessage),
+    //Registers a frontend, which receives a full snapshot before any further event
+    Subscribe(Box<dyn IUIMessageSubscriber>),
+    //Asks for the current state, which is answered through the received channel
+    RequestSnapshot(mpsc::Sender<UISnapshot>),
+}
+
+pub fn new_event_bus() -> (EventBusSender, EventBusWorker) {
+    let (tx, rx) = mpsc::channel();
+    (
+        EventBusSender { sender: tx },
+        EventBusWorker {
+            receiver: rx,
+            snapshot: UISnapshot::default(),
+            subscribers: Vec::new(),
+        },
+    )
+}
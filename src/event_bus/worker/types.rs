@@ -0,0 +1,109 @@
+use crate::event_bus::types::{EventBusMessage, IUIMessageSubscriber};
+use crate::event_bus::UIMessage;
+use crate::event_bus::UISnapshot;
+use crate::logger::CustomLogger;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvError;
+
+const LOGGER: CustomLogger = CustomLogger::init("Event Bus");
+
+pub struct EventBusWorker {
+    pub receiver: Receiver<EventBusMessage>,
+    pub snapshot: UISnapshot,
+    pub subscribers: Vec<Box<dyn IUIMessageSubscriber>>,
+}
+
+impl EventBusWorker {
+    fn publish(&mut self, message: UIMessage) {
+        self.snapshot.apply(&message);
+        self.subscribers
+            .retain(|subscriber| subscriber.notify(message.clone()));
+    }
+
+    // a new subscriber knows nothing, so it gets the whole state before any incremental message
+    fn subscribe(&mut self, subscriber: Box<dyn IUIMessageSubscriber>) {
+        if subscriber.notify(UIMessage::Snapshot(self.snapshot.clone())) {
+            self.subscribers.push(subscriber);
+            LOGGER.info(format!(
+                "New UI subscriber, {} subscribers connected",
+                self.subscribers.len()
+            ));
+        }
+    }
+
+    /// Listens for events until every sender of the bus is dropped
+    pub fn listen(&mut self) -> Result<(), RecvError> {
+        loop {
+            match self.receiver.recv()? {
+                EventBusMessage::Publish(message) => self.publish(message),
+                EventBusMessage::Subscribe(subscriber) => self.subscribe(subscriber),
+                EventBusMessage::RequestSnapshot(reply) => {
+                    let _ = reply.send(self.snapshot.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_bus::new_event_bus;
+    use crate::metainfo::{Info, Metainfo};
+    use std::sync::mpsc;
+
+    fn metainfo_named(name: &str) -> Metainfo {
+        Metainfo {
+            announce: "".to_string(),
+            announce_list: vec![],
+            info: Info {
+                piece_length: 8,
+                pieces: vec![vec![0; 20], vec![1; 20]],
+                length: 16,
+                name: name.to_string(),
+                files: None,
+            },
+            info_hash: vec![],
+        }
+    }
+
+    #[test]
+    fn late_subscriber_receives_snapshot_before_new_events() {
+        let (sender, mut worker) = new_event_bus();
+        sender.publish(UIMessage::AddTorrent(metainfo_named("debian")));
+        sender.publish(UIMessage::PieceDownloaded("debian".to_string(), vec![1]));
+
+        let (tx, rx) = mpsc::channel();
+        sender.subscribe(tx);
+        sender.publish(UIMessage::NewConnection("debian".to_string()));
+        drop(sender);
+        let _ = worker.listen();
+
+        let messages: Vec<UIMessage> = rx.try_iter().collect();
+        assert_eq!(messages.len(), 2);
+        match &messages[0] {
+            UIMessage::Snapshot(snapshot) => {
+                assert_eq!(snapshot.torrents.len(), 1);
+                assert_eq!(snapshot.torrents[0].downloaded_pieces, 1);
+                assert_eq!(snapshot.torrents[0].active_connections, 0);
+            }
+            _ => panic!("first message should be a snapshot"),
+        }
+        assert!(matches!(messages[1], UIMessage::NewConnection(_)));
+    }
+
+    #[test]
+    fn disconnected_subscriber_is_dropped() {
+        let (sender, mut worker) = new_event_bus();
+        let (tx, rx) = mpsc::channel();
+        sender.subscribe(tx);
+        sender.publish(UIMessage::AddTorrent(metainfo_named("debian")));
+        drop(sender);
+        let _ = worker.listen();
+        assert_eq!(worker.subscribers.len(), 1);
+
+        drop(rx);
+        worker.publish(UIMessage::NewConnection("debian".to_string()));
+        assert!(worker.subscribers.is_empty());
+    }
+}
@@ -1,5 +1,12 @@
 pub const SEPARATOR: &[u8] = b"\r\n\r\n";
+pub const LINE_SEPARATOR: &[u8] = b"\r\n";
 pub const URN_SEPARATOR: &str = "://";
 pub const HOST_SEPARATOR: char = '/';
 pub const REQUEST_TIMEOUT: u64 = 100;
 pub const MAX_RETRIES: u8 = 3;
+/// Largest response body accepted from a server
+pub const MAX_RESPONSE_LENGTH: usize = 8 << 20;
+/// Largest status line and headers accepted from a server
+pub const MAX_HEADERS_LENGTH: usize = 16 << 10;
+/// Largest websocket frame or reassembled message accepted from the other end
+pub const MAX_WEBSOCKET_MESSAGE_LENGTH: u64 = 1 << 20;
@@ -1,7 +1,7 @@
 use std::error;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HttpsServiceError(pub String);
 
 impl error::Error for HttpsServiceError {}
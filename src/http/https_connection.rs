@@ -5,7 +5,7 @@ use crate::boxed_result::BoxedResult;
 use log::*;
 use native_tls::{TlsConnector, TlsStream};
 use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::net::{TcpStream, ToSocketAddrs};
 use std::time::Duration;
 
 pub enum CustomTcpStream {
@@ -27,12 +27,52 @@ impl CustomTcpStream {
             CustomTcpStream::Http(stream) => stream.write_all(buf),
         }
     }
+
+    // opens a plain or tls stream to the host of the url depending on its scheme
+    pub fn connect(
+        url: &str,
+        host: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<CustomTcpStream, HttpsServiceError> {
+        let stream = match connect_timeout {
+            Some(timeout) => {
+                let address = host.to_socket_addrs()?.next().ok_or_else(|| {
+                    HttpsServiceError(format!("Could not resolve host: {}", host))
+                })?;
+                TcpStream::connect_timeout(&address, timeout)?
+            }
+            None => TcpStream::connect(host)?,
+        };
+        stream.set_write_timeout(Some(Duration::new(REQUEST_TIMEOUT, 0)))?;
+        stream.set_read_timeout(Some(Duration::new(REQUEST_TIMEOUT, 0)))?;
+
+        if url.starts_with("https://") || url.starts_with("wss://") {
+            let connector = TlsConnector::new()?;
+            let stream = connector.connect(&HttpsService::remove_port_from_host(host), stream)?;
+            Ok(CustomTcpStream::Https(stream))
+        } else {
+            Ok(CustomTcpStream::Http(stream))
+        }
+    }
+}
+
+impl Read for CustomTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        match self {
+            CustomTcpStream::Https(stream) => stream.read(buf),
+            CustomTcpStream::Http(stream) => stream.read(buf),
+        }
+    }
 }
 
 pub struct HttpsService {
     stream: CustomTcpStream,
+    url: String,
     host: String,
     max_retries: u8,
+    // keep alive services reuse the stream between requests, and reopen it if it was closed
+    keep_alive: bool,
+    is_open: bool,
 }
 
 impl HttpsService {
@@ -42,27 +82,22 @@ impl HttpsService {
 
         let host = HttpsService::url_to_host(url)?;
         trace!("host: {}", host);
-        let stream = TcpStream::connect(&host)?;
-        stream.set_write_timeout(Some(Duration::new(REQUEST_TIMEOUT, 0)))?;
-        stream.set_read_timeout(Some(Duration::new(REQUEST_TIMEOUT, 0)))?;
+        let stream = CustomTcpStream::connect(url, &host, None)?;
+        Ok(HttpsService {
+            stream,
+            url: url.to_string(),
+            host,
+            max_retries: MAX_RETRIES,
+            keep_alive: false,
+            is_open: true,
+        })
+    }
 
-        if url.starts_with("https://") {
-            let connector = TlsConnector::new()?;
-            let stream = connector.connect(&Self::remove_port_from_host(&host), stream)?;
-            let stream = CustomTcpStream::Https(stream);
-            Ok(HttpsService {
-                stream,
-                host,
-                max_retries: MAX_RETRIES,
-            })
-        } else {
-            let stream = CustomTcpStream::Http(stream);
-            Ok(HttpsService {
-                stream,
-                host,
-                max_retries: MAX_RETRIES,
-            })
-        }
+    /// Creates a service that keeps the connection open between requests
+    pub fn keep_alive_from_url(url: &str) -> Result<HttpsService, HttpsServiceError> {
+        let mut service = HttpsService::from_url(url)?;
+        service.keep_alive = true;
+        Ok(service)
     }
 
     pub fn remove_port_from_host(host: &str) -> String {
@@ -78,7 +113,7 @@ impl HttpsService {
         start_index.map(|i| bytes[i + 4..].to_vec())
     }
 
-    fn url_to_host(url: &str) -> BoxedResult<String> {
+    pub(crate) fn url_to_host(url: &str) -> BoxedResult<String> {
         let urn = url
             .split(URN_SEPARATOR)
             .nth(1)
@@ -95,7 +130,144 @@ impl HttpsService {
         Ok(host.into())
     }
 
+    fn check_headers_length(length: usize) -> BoxedResult<()> {
+        if length > MAX_HEADERS_LENGTH {
+            return Err(Box::new(HttpsServiceError(format!(
+                "Headers over the {} bytes limit",
+                MAX_HEADERS_LENGTH
+            ))));
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> BoxedResult<Vec<u8>> {
+        let mut line = vec![];
+        let mut byte = [0u8; 1];
+        while !line.ends_with(LINE_SEPARATOR) {
+            Self::check_headers_length(line.len())?;
+            self.stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        line.truncate(line.len() - LINE_SEPARATOR.len());
+        Ok(line)
+    }
+
+    fn read_headers(&mut self) -> BoxedResult<Vec<String>> {
+        let mut headers = vec![];
+        let mut length = 0;
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                return Ok(headers);
+            }
+            length += line.len() + LINE_SEPARATOR.len();
+            Self::check_headers_length(length)?;
+            headers.push(String::from_utf8_lossy(&line).to_string());
+        }
+    }
+
+    // The status line of a reused connection may be anything the server left in it, like a
+    // timeout response, so the request is only answered by a valid one
+    fn check_status_line(&self, status_line: &str) -> BoxedResult<()> {
+        let mut parts = status_line.splitn(3, ' ');
+        let version = parts.next().unwrap_or_default();
+        let status = parts.next().unwrap_or_default();
+        let is_valid = version.starts_with("HTTP/1.")
+            && status.len() == 3
+            && status.bytes().all(|byte| byte.is_ascii_digit());
+        if !is_valid || status == "408" {
+            return Err(Box::new(HttpsServiceError(format!(
+                "Invalid status line from {}: {}",
+                self.host, status_line
+            ))));
+        }
+        Ok(())
+    }
+
+    fn header_value<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+        headers.iter().find_map(|header| {
+            let (header_name, value) = header.split_once(':')?;
+            if header_name.trim().eq_ignore_ascii_case(name) {
+                Some(value.trim())
+            } else {
+                None
+            }
+        })
+    }
+
+    fn check_response_length(length: usize) -> BoxedResult<()> {
+        if length > MAX_RESPONSE_LENGTH {
+            return Err(Box::new(HttpsServiceError(format!(
+                "Response of {} bytes is over the {} bytes limit",
+                length, MAX_RESPONSE_LENGTH
+            ))));
+        }
+        Ok(())
+    }
+
+    fn read_chunked_body(&mut self) -> BoxedResult<Vec<u8>> {
+        let mut body = vec![];
+        loop {
+            let size_line = String::from_utf8_lossy(&self.read_line()?).to_string();
+            let size_digits = size_line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size_digits, 16)?;
+            Self::check_response_length(body.len().saturating_add(size))?;
+            if size == 0 {
+                // trailers, up to the empty line
+                self.read_headers()?;
+                return Ok(body);
+            }
+            let mut chunk = vec![0; size];
+            self.stream.read_exact(&mut chunk)?;
+            body.extend(chunk);
+            self.read_line()?;
+        }
+    }
+
+    fn try_keep_alive_request(&mut self, request: &str) -> BoxedResult<Vec<u8>> {
+        if !self.is_open {
+            trace!("reopening connection with host: {}", self.host);
+            self.stream = CustomTcpStream::connect(&self.url, &self.host, None)?;
+        }
+        // the stream can only be reused once the whole response is read
+        self.is_open = false;
+        self.stream.write_all(request.as_bytes())?;
+
+        let status_line = String::from_utf8_lossy(&self.read_line()?).to_string();
+        self.check_status_line(&status_line)?;
+        let headers = self.read_headers()?;
+        let content_length = Self::header_value(&headers, "content-length");
+        let is_chunked = Self::header_value(&headers, "transfer-encoding")
+            .map(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false);
+        let body = if let Some(content_length) = content_length {
+            let content_length = content_length.parse()?;
+            Self::check_response_length(content_length)?;
+            let mut body = vec![0; content_length];
+            self.stream.read_exact(&mut body)?;
+            body
+        } else if is_chunked {
+            self.read_chunked_body()?
+        } else {
+            // without a length, the body ends when the server closes the connection
+            let mut body = vec![];
+            (&mut self.stream)
+                .take(MAX_RESPONSE_LENGTH as u64 + 1)
+                .read_to_end(&mut body)?;
+            Self::check_response_length(body.len())?;
+            return Ok(body);
+        };
+
+        self.is_open = !Self::header_value(&headers, "connection")
+            .map(|connection| connection.eq_ignore_ascii_case("close"))
+            .unwrap_or(false);
+        Ok(body)
+    }
+
     fn try_request(&mut self, request: &str) -> BoxedResult<Vec<u8>> {
+        if self.keep_alive {
+            return self.try_keep_alive_request(request);
+        }
         self.stream.write_all(request.as_bytes())?;
         let mut response = vec![];
         self.stream.read_to_end(&mut response)?;
@@ -112,9 +284,14 @@ impl HttpsService {
 
 impl IHttpService for HttpsService {
     fn get(&mut self, path: &str, query_params: &str) -> Result<Vec<u8>, HttpsServiceError> {
+        let connection_header = if self.keep_alive {
+            "Connection: keep-alive\r\n"
+        } else {
+            ""
+        };
         let request = format!(
-            "GET {}?{} HTTP/1.1\r\nHost: {}\r\n\r\n",
-            path, query_params, self.host
+            "GET {}?{} HTTP/1.1\r\nHost: {}\r\n{}\r\n",
+            path, query_params, self.host, connection_header
         );
         let mut retries = 0;
         loop {
@@ -146,3 +323,144 @@ impl IHttpService for MockHttpsService {
         Ok(self.read_bytes.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn read_request(socket: &mut TcpStream) -> String {
+        let mut request = vec![];
+        let mut byte = [0u8; 1];
+        while !request.ends_with(SEPARATOR) {
+            socket.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        String::from_utf8(request).unwrap()
+    }
+
+    #[test]
+    fn keep_alive_service_reuses_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let first = read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst")
+                .unwrap();
+            let second = read_request(&mut socket);
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nsec\r\n3\r\nond\r\n0\r\n\r\n",
+                )
+                .unwrap();
+            (first, second)
+        });
+
+        let mut service =
+            HttpsService::keep_alive_from_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+        let first = service.get("/announce", "a=1").unwrap();
+        let second = service.get("/announce", "a=2").unwrap();
+        let (first_request, second_request) = server.join().unwrap();
+
+        assert_eq!(first, b"first");
+        assert_eq!(second, b"second");
+        assert!(first_request.starts_with("GET /announce?a=1 HTTP/1.1"));
+        assert!(first_request.contains("Connection: keep-alive"));
+        assert!(second_request.starts_with("GET /announce?a=2 HTTP/1.1"));
+    }
+
+    #[test]
+    fn keep_alive_service_rejects_huge_content_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 99999999999\r\n\r\n")
+                .unwrap();
+        });
+
+        let mut service =
+            HttpsService::keep_alive_from_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+        service.max_retries = 0;
+
+        assert!(service.get("/announce", "").is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn keep_alive_service_reconnects_after_connection_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            for body in ["one", "two"] {
+                let (mut socket, _) = listener.accept().unwrap();
+                read_request(&mut socket);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 3\r\n\r\n{}",
+                    body
+                );
+                socket.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let mut service =
+            HttpsService::keep_alive_from_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+
+        assert_eq!(service.get("/announce", "").unwrap(), b"one");
+        assert_eq!(service.get("/announce", "").unwrap(), b"two");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn keep_alive_service_rejects_huge_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&mut socket);
+            let header = format!("X-Padding: {}\r\n", "a".repeat(MAX_HEADERS_LENGTH));
+            let _ = socket.write_all(format!("HTTP/1.1 200 OK\r\n{}", header).as_bytes());
+        });
+
+        let mut service =
+            HttpsService::keep_alive_from_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+        service.max_retries = 0;
+
+        assert!(service.get("/announce", "").is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn keep_alive_service_reconnects_after_invalid_status_line() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\none")
+                .unwrap();
+            read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 408 Request Timeout\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            drop(socket);
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\ntwo")
+                .unwrap();
+        });
+
+        let mut service =
+            HttpsService::keep_alive_from_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+
+        assert_eq!(service.get("/announce", "").unwrap(), b"one");
+        assert_eq!(service.get("/announce", "").unwrap(), b"two");
+        server.join().unwrap();
+    }
+}
@@ -2,9 +2,11 @@ mod constants;
 mod errors;
 mod https_connection;
 mod types;
+mod websocket;
 
 pub use errors::HttpsServiceError;
 pub use https_connection::HttpsService;
 #[cfg(test)]
 pub use https_connection::MockHttpsService;
 pub use types::IHttpService;
+pub use websocket::{WebSocketMessage, WebSocketStream};
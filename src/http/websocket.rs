@@ -0,0 +1,389 @@
+use super::constants::*;
+use super::errors::HttpsServiceError;
+use super::https_connection::{CustomTcpStream, HttpsService};
+use log::*;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+use std::io::Read;
+use std::time::Duration;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const FIN_BIT: u8 = 0x80;
+const MASK_BIT: u8 = 0x80;
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Client side of a WebSocket connection (RFC 6455), over plain tcp for `ws://` urls
+/// and over tls for `wss://` urls
+pub struct WebSocketStream {
+    stream: CustomTcpStream,
+    host: String,
+}
+
+impl WebSocketStream {
+    pub fn connect(
+        url: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<WebSocketStream, HttpsServiceError> {
+        debug!("Opening websocket connection to url: {}", url);
+        let host = HttpsService::url_to_host(url)?;
+        let mut websocket = WebSocketStream {
+            stream: CustomTcpStream::connect(url, &host, connect_timeout)?,
+            host,
+        };
+        websocket.handshake(&Self::url_to_path(url))?;
+        Ok(websocket)
+    }
+
+    fn url_to_path(url: &str) -> String {
+        let urn = url.split(URN_SEPARATOR).nth(1).unwrap_or_default();
+        match urn.find(HOST_SEPARATOR) {
+            Some(index) => urn[index..].to_string(),
+            None => HOST_SEPARATOR.to_string(),
+        }
+    }
+
+    fn handshake(&mut self, path: &str) -> Result<(), HttpsServiceError> {
+        let key = base64_encode(&rand::thread_rng().gen::<[u8; 16]>());
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path,
+            HttpsService::remove_port_from_host(&self.host),
+            key
+        );
+        self.stream.write_all(request.as_bytes())?;
+
+        // the response has no body, so it ends with the first empty line
+        let mut response = vec![];
+        let mut byte = [0u8; 1];
+        while !response.ends_with(SEPARATOR) {
+            if response.len() > MAX_HEADERS_LENGTH {
+                return Err(HttpsServiceError(format!(
+                    "Websocket upgrade response from {} is over the {} bytes limit",
+                    self.host, MAX_HEADERS_LENGTH
+                )));
+            }
+            self.stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        if !status_line.contains(" 101 ") {
+            return Err(HttpsServiceError(format!(
+                "Websocket upgrade rejected by {}: {}",
+                self.host, status_line
+            )));
+        }
+        let expected_accept = accept_key(&key);
+        let accepted = response.lines().any(|line| {
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or_default().trim();
+            let value = header.next().unwrap_or_default().trim();
+            name.eq_ignore_ascii_case("sec-websocket-accept") && value == expected_accept
+        });
+        if !accepted {
+            return Err(HttpsServiceError(format!(
+                "Invalid Sec-WebSocket-Accept from {}",
+                self.host
+            )));
+        }
+        Ok(())
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), HttpsServiceError> {
+        let mask = rand::thread_rng().gen::<[u8; 4]>();
+        self.stream
+            .write_all(&encode_frame(opcode, payload, mask))?;
+        Ok(())
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), HttpsServiceError> {
+        self.send_frame(OPCODE_TEXT, text.as_bytes())
+    }
+
+    pub fn send_binary(&mut self, bytes: &[u8]) -> Result<(), HttpsServiceError> {
+        self.send_frame(OPCODE_BINARY, bytes)
+    }
+
+    /// Reads the next data message, answering pings and joining fragmented messages
+    pub fn read_message(&mut self) -> Result<WebSocketMessage, HttpsServiceError> {
+        let mut message_opcode = None;
+        let mut payload = vec![];
+        loop {
+            let frame = read_frame(&mut self.stream)?;
+            match frame.opcode {
+                OPCODE_PING => self.send_frame(OPCODE_PONG, &frame.payload)?,
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    let _ = self.send_frame(OPCODE_CLOSE, &[]);
+                    return Err(HttpsServiceError(format!(
+                        "Websocket closed by {}",
+                        self.host
+                    )));
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    if frame.opcode != OPCODE_CONTINUATION {
+                        message_opcode = Some(frame.opcode);
+                    }
+                    if (payload.len() + frame.payload.len()) as u64 > MAX_WEBSOCKET_MESSAGE_LENGTH {
+                        return Err(HttpsServiceError(format!(
+                            "Websocket message from {} is over the {} bytes limit",
+                            self.host, MAX_WEBSOCKET_MESSAGE_LENGTH
+                        )));
+                    }
+                    payload.extend(frame.payload);
+                    if frame.fin {
+                        break;
+                    }
+                }
+                opcode => {
+                    return Err(HttpsServiceError(format!(
+                        "Unknown websocket opcode {}",
+                        opcode
+                    )))
+                }
+            }
+        }
+        match message_opcode {
+            Some(OPCODE_TEXT) => String::from_utf8(payload)
+                .map(WebSocketMessage::Text)
+                .map_err(|_| HttpsServiceError("Websocket text is not utf8".to_string())),
+            Some(_) => Ok(WebSocketMessage::Binary(payload)),
+            None => Err(HttpsServiceError(
+                "Websocket continuation without a message".to_string(),
+            )),
+        }
+    }
+
+    pub fn close(&mut self) {
+        let _ = self.send_frame(OPCODE_CLOSE, &[]);
+    }
+}
+
+// client frames are always masked
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![FIN_BIT | opcode];
+    match payload.len() {
+        length if length < 126 => frame.push(MASK_BIT | length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(MASK_BIT | 126);
+            frame.extend((length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(MASK_BIT | 127);
+            frame.extend((length as u64).to_be_bytes());
+        }
+    }
+    frame.extend(mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    frame
+}
+
+fn read_frame(reader: &mut impl Read) -> Result<Frame, HttpsServiceError> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+    let length = match header[1] & !MASK_BIT {
+        126 => {
+            let mut length = [0u8; 2];
+            reader.read_exact(&mut length)?;
+            u16::from_be_bytes(length) as u64
+        }
+        127 => {
+            let mut length = [0u8; 8];
+            reader.read_exact(&mut length)?;
+            u64::from_be_bytes(length)
+        }
+        length => length as u64,
+    };
+    if length > MAX_WEBSOCKET_MESSAGE_LENGTH {
+        return Err(HttpsServiceError(format!(
+            "Websocket frame of {} bytes is over the {} bytes limit",
+            length, MAX_WEBSOCKET_MESSAGE_LENGTH
+        )));
+    }
+    let mask = if header[1] & MASK_BIT != 0 {
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask)?;
+        Some(mask)
+    } else {
+        None
+    };
+    let mut payload = vec![];
+    reader.take(length).read_to_end(&mut payload)?;
+    if payload.len() as u64 != length {
+        return Err(HttpsServiceError(
+            "Unexpected end of stream while reading websocket frame".to_string(),
+        ));
+    }
+    if let Some(mask) = mask {
+        payload
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, byte)| *byte ^= mask[i % 4]);
+    }
+    Ok(Frame {
+        fin: header[0] & FIN_BIT != 0,
+        opcode: header[0] & 0x0F,
+        payload,
+    })
+}
+
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{}{}", key, WEBSOCKET_GUID).as_bytes());
+    base64_encode(&hasher.finalize())
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3F;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_accept_key_from_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn base64_pads_incomplete_groups() {
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn encoded_frames_are_read_back_unmasked() {
+        for length in [0, 125, 126, 70_000] {
+            let payload: Vec<u8> = (0..length).map(|i| i as u8).collect();
+            let frame = encode_frame(OPCODE_BINARY, &payload, [1, 2, 3, 4]);
+
+            let read = read_frame(&mut frame.as_slice()).unwrap();
+
+            assert_eq!(
+                read,
+                Frame {
+                    fin: true,
+                    opcode: OPCODE_BINARY,
+                    payload
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn read_frame_fails_on_truncated_payload() {
+        let frame = encode_frame(OPCODE_TEXT, b"hello", [0; 4]);
+        assert!(read_frame(&mut &frame[..frame.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn read_frame_fails_over_max_length() {
+        let mut header = vec![FIN_BIT | OPCODE_BINARY, 127];
+        header.extend(u64::MAX.to_be_bytes());
+        assert!(read_frame(&mut header.as_slice()).is_err());
+    }
+
+    #[test]
+    fn exchanges_messages_with_a_local_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut byte = [0u8; 1];
+            while !request.ends_with(SEPARATOR) {
+                socket.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            let request = String::from_utf8(request).unwrap();
+            let key = request
+                .lines()
+                .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+                .unwrap();
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key)
+            );
+            std::io::Write::write_all(&mut socket, response.as_bytes()).unwrap();
+            // a ping and a message split in two frames, unmasked as servers send them
+            let frames: Vec<u8> = [
+                vec![FIN_BIT | OPCODE_PING, 1, b'p'],
+                vec![OPCODE_TEXT, 3, b'h', b'e', b'l'],
+                vec![FIN_BIT | OPCODE_CONTINUATION, 2, b'l', b'o'],
+            ]
+            .concat();
+            std::io::Write::write_all(&mut socket, &frames).unwrap();
+
+            let pong = read_frame(&mut socket).unwrap();
+            let text = read_frame(&mut socket).unwrap();
+            (pong, text)
+        });
+
+        let mut websocket =
+            WebSocketStream::connect(&format!("ws://127.0.0.1:{}", port), None).unwrap();
+        let message = websocket.read_message().unwrap();
+        websocket.send_text("announce").unwrap();
+        let (pong, text) = server.join().unwrap();
+
+        assert_eq!(message, WebSocketMessage::Text("hello".to_string()));
+        assert_eq!(pong.opcode, OPCODE_PONG);
+        assert_eq!(pong.payload, b"p");
+        assert_eq!(text.payload, b"announce");
+    }
+
+    #[test]
+    fn path_defaults_to_root() {
+        assert_eq!(
+            WebSocketStream::url_to_path("wss://tracker.example.com"),
+            "/"
+        );
+        assert_eq!(
+            WebSocketStream::url_to_path("wss://tracker.example.com:8000/announce"),
+            "/announce"
+        );
+    }
+}
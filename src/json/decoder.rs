@@ -0,0 +1,323 @@
+use super::errors::JsonDecoderError;
+use super::types::JsonValue;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+const OBJECT_START_TOKEN: char = '{';
+const OBJECT_END_TOKEN: char = '}';
+const ARRAY_START_TOKEN: char = '[';
+const ARRAY_END_TOKEN: char = ']';
+const STRING_TOKEN: char = '"';
+const KEY_SEPARATOR: char = ':';
+const VALUE_SEPARATOR: char = ',';
+const ESCAPE_TOKEN: char = '\\';
+/// Arrays and objects nested deeper than this are rejected, so a hostile text can not
+/// overflow the stack
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+type Chars<'a> = Peekable<CharIndices<'a>>;
+
+/// Decodes a json text into a [`JsonValue`]
+///
+/// ## Example
+///
+/// ```
+/// use bittorrent_rustico::json::{decode, JsonValue};
+///
+/// let decoded = decode("[1, \"a\"]").unwrap();
+/// assert_eq!(
+///     decoded,
+///     JsonValue::Array(vec![JsonValue::Integer(1), JsonValue::String("a".to_string())])
+/// );
+/// ```
+pub fn decode(text: &str) -> Result<JsonValue, JsonDecoderError> {
+    let mut chars = text.char_indices().peekable();
+    let value = read_value(&mut chars, 0)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some((idx, c)) => Err(JsonDecoderError(format!(
+            "Unexpected trailing {} at position {}",
+            c, idx
+        ))),
+    }
+}
+
+fn skip_whitespace(chars: &mut Chars) {
+    while let Some((_, c)) = chars.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Chars, expected: char) -> Result<(), JsonDecoderError> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some((_, c)) if c == expected => Ok(()),
+        Some((idx, c)) => Err(JsonDecoderError(format!(
+            "Expected {} but got {} at position {}",
+            expected, c, idx
+        ))),
+        None => Err(JsonDecoderError(format!(
+            "Unexpected end of stream, expected {}",
+            expected
+        ))),
+    }
+}
+
+// depth is the amount of arrays and objects the value is nested in
+fn read_value(chars: &mut Chars, depth: usize) -> Result<JsonValue, JsonDecoderError> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some((idx, OBJECT_START_TOKEN | ARRAY_START_TOKEN)) if depth >= MAX_NESTING_DEPTH => {
+            Err(JsonDecoderError(format!(
+                "Nesting deeper than {} at position {}",
+                MAX_NESTING_DEPTH, idx
+            )))
+        }
+        Some((_, OBJECT_START_TOKEN)) => read_object(chars, depth + 1).map(JsonValue::Object),
+        Some((_, ARRAY_START_TOKEN)) => read_array(chars, depth + 1).map(JsonValue::Array),
+        Some((_, STRING_TOKEN)) => read_string(chars).map(JsonValue::String),
+        Some((_, '-' | '0'..='9')) => read_number(chars),
+        Some((_, 't')) => read_literal(chars, "true", JsonValue::Bool(true)),
+        Some((_, 'f')) => read_literal(chars, "false", JsonValue::Bool(false)),
+        Some((_, 'n')) => read_literal(chars, "null", JsonValue::Null),
+        Some((idx, c)) => Err(JsonDecoderError(format!(
+            "Unknown token {} at position {}",
+            c, idx
+        ))),
+        None => Err(JsonDecoderError("Unexpected end of stream".to_string())),
+    }
+}
+
+fn read_literal(
+    chars: &mut Chars,
+    literal: &str,
+    value: JsonValue,
+) -> Result<JsonValue, JsonDecoderError> {
+    for expected in literal.chars() {
+        match chars.next() {
+            Some((_, c)) if c == expected => {}
+            _ => {
+                return Err(JsonDecoderError(format!(
+                    "Invalid literal, expected {}",
+                    literal
+                )))
+            }
+        }
+    }
+    Ok(value)
+}
+
+fn read_number(chars: &mut Chars) -> Result<JsonValue, JsonDecoderError> {
+    let mut number = String::new();
+    while let Some((_, c)) = chars.peek() {
+        match c {
+            '-' | '+' | '.' | 'e' | 'E' | '0'..='9' => number.push(*c),
+            _ => break,
+        }
+        chars.next();
+    }
+    if let Ok(integer) = number.parse::<i64>() {
+        return Ok(JsonValue::Integer(integer));
+    }
+    number
+        .parse::<f64>()
+        .map(JsonValue::Float)
+        .map_err(|_| JsonDecoderError(format!("Invalid number {}", number)))
+}
+
+fn read_unicode_escape(chars: &mut Chars) -> Result<u32, JsonDecoderError> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|(_, c)| c.to_digit(16))
+            .ok_or_else(|| JsonDecoderError("Invalid unicode escape".to_string()))?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+fn read_escape(chars: &mut Chars) -> Result<char, JsonDecoderError> {
+    match chars.next() {
+        Some((_, '"')) => Ok('"'),
+        Some((_, '\\')) => Ok('\\'),
+        Some((_, '/')) => Ok('/'),
+        Some((_, 'b')) => Ok('\u{8}'),
+        Some((_, 'f')) => Ok('\u{c}'),
+        Some((_, 'n')) => Ok('\n'),
+        Some((_, 'r')) => Ok('\r'),
+        Some((_, 't')) => Ok('\t'),
+        Some((_, 'u')) => {
+            let code = read_unicode_escape(chars)?;
+            // characters outside the basic plane come as a surrogate pair
+            let code = if (0xD800..0xDC00).contains(&code) {
+                if !matches!(
+                    (chars.next(), chars.next()),
+                    (Some((_, ESCAPE_TOKEN)), Some((_, 'u')))
+                ) {
+                    return Err(JsonDecoderError("Unpaired surrogate escape".to_string()));
+                }
+                let low = read_unicode_escape(chars)?;
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(JsonDecoderError(format!(
+                        "Invalid low surrogate escape {:X}",
+                        low
+                    )));
+                }
+                0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+            } else {
+                code
+            };
+            char::from_u32(code)
+                .ok_or_else(|| JsonDecoderError(format!("Invalid unicode code point {}", code)))
+        }
+        Some((idx, c)) => Err(JsonDecoderError(format!(
+            "Invalid escape {} at position {}",
+            c, idx
+        ))),
+        None => Err(JsonDecoderError(
+            "Unexpected end of stream while reading escape".to_string(),
+        )),
+    }
+}
+
+fn read_string(chars: &mut Chars) -> Result<String, JsonDecoderError> {
+    expect(chars, STRING_TOKEN)?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some((_, STRING_TOKEN)) => break,
+            Some((_, ESCAPE_TOKEN)) => string.push(read_escape(chars)?),
+            Some((_, c)) => string.push(c),
+            None => {
+                return Err(JsonDecoderError(
+                    "Unexpected end of stream while reading string".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(string)
+}
+
+fn read_array(chars: &mut Chars, depth: usize) -> Result<Vec<JsonValue>, JsonDecoderError> {
+    expect(chars, ARRAY_START_TOKEN)?;
+    let mut array = vec![];
+    skip_whitespace(chars);
+    if let Some((_, ARRAY_END_TOKEN)) = chars.peek() {
+        chars.next();
+        return Ok(array);
+    }
+    loop {
+        array.push(read_value(chars, depth)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some((_, VALUE_SEPARATOR)) => continue,
+            Some((_, ARRAY_END_TOKEN)) => break,
+            _ => return Err(JsonDecoderError("Invalid array".to_string())),
+        }
+    }
+    Ok(array)
+}
+
+fn read_object(
+    chars: &mut Chars,
+    depth: usize,
+) -> Result<HashMap<String, JsonValue>, JsonDecoderError> {
+    expect(chars, OBJECT_START_TOKEN)?;
+    let mut object = HashMap::new();
+    skip_whitespace(chars);
+    if let Some((_, OBJECT_END_TOKEN)) = chars.peek() {
+        chars.next();
+        return Ok(object);
+    }
+    loop {
+        skip_whitespace(chars);
+        let key = read_string(chars)?;
+        expect(chars, KEY_SEPARATOR)?;
+        object.insert(key, read_value(chars, depth)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some((_, VALUE_SEPARATOR)) => continue,
+            Some((_, OBJECT_END_TOKEN)) => break,
+            _ => return Err(JsonDecoderError("Invalid object".to_string())),
+        }
+    }
+    Ok(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_literals() {
+        assert_eq!(decode("true").unwrap(), JsonValue::Bool(true));
+        assert_eq!(decode("false").unwrap(), JsonValue::Bool(false));
+        assert_eq!(decode(" null ").unwrap(), JsonValue::Null);
+    }
+
+    #[test]
+    fn decodes_numbers() {
+        assert_eq!(decode("-120").unwrap(), JsonValue::Integer(-120));
+        assert_eq!(decode("1.5").unwrap(), JsonValue::Float(1.5));
+        assert!(decode("1.2.3").is_err());
+    }
+
+    #[test]
+    fn decodes_string_with_escapes() {
+        assert_eq!(
+            decode(r#""a\"b\\cé\n""#).unwrap(),
+            JsonValue::String("a\"b\\c\u{e9}\n".to_string())
+        );
+    }
+
+    #[test]
+    fn decodes_surrogate_pairs() {
+        assert_eq!(
+            decode(r#""\ud83d\ude00""#).unwrap(),
+            JsonValue::String("\u{1F600}".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_fails_on_invalid_low_surrogate() {
+        assert!(decode(r#""\ud83d\u0041""#).is_err());
+        assert!(decode(r#""\ud83d\ud83d""#).is_err());
+    }
+
+    #[test]
+    fn decode_fails_past_max_nesting_depth() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(decode(&nested(MAX_NESTING_DEPTH)).is_ok());
+        assert!(decode(&nested(MAX_NESTING_DEPTH + 1)).is_err());
+        assert!(decode(&"[".repeat(1_000_000)).is_err());
+    }
+
+    #[test]
+    fn decodes_nested_objects_and_arrays() {
+        let decoded =
+            decode(r#"{"interval": 120, "peers": [{"ip": "1.2.3.4"}], "x": []}"#).unwrap();
+        let object = decoded.get_as_object().unwrap();
+        assert_eq!(object["interval"], JsonValue::Integer(120));
+        let peers = object["peers"].get_as_array().unwrap();
+        assert_eq!(
+            peers[0].get_as_object().unwrap()["ip"],
+            JsonValue::String("1.2.3.4".to_string())
+        );
+        assert_eq!(object["x"], JsonValue::Array(vec![]));
+    }
+
+    #[test]
+    fn decode_fails_on_invalid_input() {
+        assert!(decode("").is_err());
+        assert!(decode("{\"a\" 1}").is_err());
+        assert!(decode("[1, 2").is_err());
+        assert!(decode("\"abc").is_err());
+        assert!(decode("{} {}").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use super::types::JsonValue;
+
+/// Encodes a [`JsonValue`] into its json text
+///
+/// ## Example
+///
+/// ```
+/// use bittorrent_rustico::json::{encode, JsonValue};
+///
+/// let value = JsonValue::Array(vec![JsonValue::Integer(1), JsonValue::Null]);
+/// assert_eq!(encode(&value), "[1,null]");
+/// ```
+pub fn encode(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "null".to_string(),
+        JsonValue::Bool(boolean) => boolean.to_string(),
+        JsonValue::Integer(integer) => integer.to_string(),
+        JsonValue::Float(float) => float.to_string(),
+        JsonValue::String(string) => encode_string(string),
+        JsonValue::Array(array) => encode_array(array),
+        JsonValue::Object(object) => {
+            let mut keys: Vec<&String> = object.keys().collect();
+            keys.sort();
+            let members: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", encode_string(key), encode(&object[key])))
+                .collect();
+            format!("{{{}}}", members.join(","))
+        }
+    }
+}
+
+fn encode_array(array: &[JsonValue]) -> String {
+    let items: Vec<String> = array.iter().map(encode).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn encode_string(string: &str) -> String {
+    let mut encoded = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if (c as u32) < 0x20 => encoded.push_str(&format!("\\u{:04x}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::decode;
+    use std::collections::HashMap;
+
+    #[test]
+    fn encodes_object_with_sorted_keys() {
+        let mut object = HashMap::new();
+        object.insert("b".to_string(), JsonValue::Integer(2));
+        object.insert("a".to_string(), JsonValue::Bool(true));
+        assert_eq!(encode(&JsonValue::Object(object)), r#"{"a":true,"b":2}"#);
+    }
+
+    #[test]
+    fn encodes_control_characters_as_escapes() {
+        let value = JsonValue::String("\u{0}\u{1f}\"\\".to_string());
+        assert_eq!(encode(&value), r#""\u0000\u001f\"\\""#);
+    }
+
+    #[test]
+    fn encoded_value_decodes_to_the_same_value() {
+        let binary: String = (0..=255u8).map(char::from).collect();
+        let value = JsonValue::Array(vec![
+            JsonValue::String(binary),
+            JsonValue::Float(0.5),
+            JsonValue::Null,
+        ]);
+        assert_eq!(decode(&encode(&value)).unwrap(), value);
+    }
+}
@@ -0,0 +1,13 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct JsonDecoderError(pub String);
+
+impl error::Error for JsonDecoderError {}
+
+impl fmt::Display for JsonDecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Json Decoder Error: {}", self.0)
+    }
+}
@@ -0,0 +1,9 @@
+mod decoder;
+mod encoder;
+mod errors;
+mod types;
+
+pub use decoder::decode;
+pub use encoder::encode;
+pub use errors::JsonDecoderError;
+pub use types::JsonValue;
@@ -0,0 +1,61 @@
+use super::errors::*;
+use std::collections::HashMap;
+#[derive(Debug, Clone, PartialEq)]
+/// The type that is returned by the decoder
+/// and is used to represent a decoded json value
+///
+/// ## Example
+/// ```
+/// use bittorrent_rustico::json::JsonValue;
+/// let decoded_value = JsonValue::String("hola".to_string());
+///
+/// assert_eq!(decoded_value.get_as_string().unwrap(), "hola");
+/// ```
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(HashMap<String, JsonValue>),
+}
+
+impl JsonValue {
+    pub fn get_as_string(&self) -> Result<&String, JsonDecoderError> {
+        match self {
+            JsonValue::String(value) => Ok(value),
+            _ => Err(JsonDecoderError(format!(
+                "Expected a string, but got {:?}",
+                self
+            ))),
+        }
+    }
+    pub fn get_as_integer(&self) -> Result<&i64, JsonDecoderError> {
+        match self {
+            JsonValue::Integer(value) => Ok(value),
+            _ => Err(JsonDecoderError(format!(
+                "Expected an integer, but got {:?}",
+                self
+            ))),
+        }
+    }
+    pub fn get_as_array(&self) -> Result<&Vec<JsonValue>, JsonDecoderError> {
+        match self {
+            JsonValue::Array(value) => Ok(value),
+            _ => Err(JsonDecoderError(format!(
+                "Expected an array, but got {:?}",
+                self
+            ))),
+        }
+    }
+    pub fn get_as_object(&self) -> Result<&HashMap<String, JsonValue>, JsonDecoderError> {
+        match self {
+            JsonValue::Object(value) => Ok(value),
+            _ => Err(JsonDecoderError(format!(
+                "Expected an object, but got {:?}",
+                self
+            ))),
+        }
+    }
+}
@@ -5,15 +5,22 @@ pub mod client;
 pub mod config;
 pub mod constants;
 pub mod download_manager;
+pub mod event_bus;
 pub mod http;
+pub mod json;
 pub mod logger;
 pub mod metainfo;
 pub mod peer;
 pub mod peer_connection_manager;
+pub mod piece_hasher;
 pub mod piece_manager;
 pub mod piece_saver;
 pub mod server;
+pub mod session;
 pub mod tracker;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "gui")]
 pub mod ui;
 
 pub mod boxed_result {
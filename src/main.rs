@@ -1,45 +1,132 @@
 use bittorrent_rustico::application::run_with_torrent;
-use bittorrent_rustico::ui::{run_ui, UIMessage};
-use gtk::{self, glib};
+use bittorrent_rustico::config::Config;
+#[cfg(any(feature = "gui", feature = "tui"))]
+use bittorrent_rustico::event_bus::new_event_bus;
+use bittorrent_rustico::event_bus::EventBusSender;
+use bittorrent_rustico::session::{SessionError, TorrentSession};
+use bittorrent_rustico::tracker::announce_scheduler::types::{
+    ANNOUNCE_SPACING, MAX_ANNOUNCE_JITTER,
+};
+use bittorrent_rustico::tracker::new_announce_scheduler;
+#[cfg(feature = "tui")]
+use bittorrent_rustico::tui::run_tui;
+#[cfg(feature = "gui")]
+use bittorrent_rustico::ui::run_ui;
 use log::*;
 use std::env;
-use std::sync::mpsc;
 use std::thread::{self, JoinHandle};
 fn main() {
     pretty_env_logger::init();
-    if env::var("UI").is_ok() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("--export-session") | Some("--import-session") => {
+            if let Err(err) = run_session_command(&args) {
+                error!("{}", err);
+            }
+            return;
+        }
+        _ => {}
+    }
+    if env::var("TUI").is_ok() {
+        run_client_with_tui();
+    } else if env::var("UI").is_ok() {
         run_client_with_ui();
     } else {
         run_client_with_no_ui();
     }
 }
 
+// --export-session <config file> <torrent file> <session file>
+// --import-session <config file> <session file> <output dir>
+fn run_session_command(args: &[String]) -> Result<(), SessionError> {
+    if args.len() != 4 {
+        return Err(SessionError::InvalidSession(
+            "expected a config file and two paths".to_string(),
+        ));
+    }
+    let config = Config::from_path(&args[1])?;
+    if args[0] == "--export-session" {
+        TorrentSession::export(&args[2], &config)?.save(&args[3])?;
+        info!("Exported session of {} to {}", args[2], args[3]);
+    } else {
+        let imported = TorrentSession::load(&args[2])?.import(&config, &args[3])?;
+        info!(
+            "Imported session, run it with: {} {}",
+            imported.config_path, imported.torrent_path
+        );
+    }
+    Ok(())
+}
+
 fn run_client_with_no_ui() {
     run_client(None);
 }
 
+#[cfg(feature = "gui")]
+fn run_client_with_ui() {
+    // the event bus keeps the client state, so the ui can (re)subscribe at any time
+    let (event_bus_sender, mut event_bus_worker) = new_event_bus();
+    thread::spawn(move || {
+        let _ = event_bus_worker.listen();
+    });
+    let ui_event_bus_sender = event_bus_sender.clone();
+    let client_handle = thread::spawn(move || {
+        run_client(Some(event_bus_sender));
+    });
+    run_ui(ui_event_bus_sender);
+    client_handle.join().unwrap();
+}
+
+#[cfg(not(feature = "gui"))]
 fn run_client_with_ui() {
-    let (client_sender, client_receiver) = mpsc::channel(); // channel necessary to pass the ui sender to the client
+    warn!("Built without the gui feature, running without UI");
+    run_client_with_no_ui();
+}
+
+#[cfg(feature = "tui")]
+fn run_client_with_tui() {
+    let (event_bus_sender, mut event_bus_worker) = new_event_bus();
+    thread::spawn(move || {
+        let _ = event_bus_worker.listen();
+    });
+    let tui_event_bus_sender = event_bus_sender.clone();
     let client_handle = thread::spawn(move || {
-        let ui_tx = client_receiver.recv().unwrap(); // receive the ui sender from the client
-        run_client(Some(ui_tx)); // run the client with the ui sender
+        run_client(Some(event_bus_sender));
     });
-    run_ui(client_sender);
+    if let Err(err) = run_tui(tui_event_bus_sender) {
+        error!("Error running the terminal UI: {}", err);
+    }
+    info!("Terminal UI closed, waiting for the downloads to finish");
     client_handle.join().unwrap();
 }
 
-fn run_client(ui_message_sender: Option<glib::Sender<UIMessage>>) {
+#[cfg(not(feature = "tui"))]
+fn run_client_with_tui() {
+    warn!("Built without the tui feature, running without UI");
+    run_client_with_no_ui();
+}
+
+fn run_client(ui_message_sender: Option<EventBusSender>) {
     let mut args = env::args().skip(1);
     let config_file = args.next().unwrap_or_else(|| "".to_string());
     // iterate through all args and call run_with_torrent for each torrent file
+    // torrents announcing to the same tracker share its schedule and connection
+    let (announce_scheduler_sender, mut announce_scheduler_worker) =
+        new_announce_scheduler(ANNOUNCE_SPACING, MAX_ANNOUNCE_JITTER);
+    let announce_scheduler_handle = thread::spawn(move || {
+        let _ = announce_scheduler_worker.listen();
+    });
     let mut torrent_handles: Vec<JoinHandle<()>> = vec![];
     for torrent_file in args {
         info!("Running with torrent file: {}", torrent_file);
         let ui_msg_sender_clone = ui_message_sender.clone();
         let torrent_file = torrent_file.to_string();
         let cfg = config_file.clone();
+        let announce_scheduler = announce_scheduler_sender.clone();
         torrent_handles.push(thread::spawn(move || {
-            if let Err(err) = run_with_torrent(&torrent_file, &cfg, ui_msg_sender_clone) {
+            if let Err(err) =
+                run_with_torrent(&torrent_file, &cfg, ui_msg_sender_clone, announce_scheduler)
+            {
                 error!("Error running with torrent file: {}", torrent_file);
                 error!("{}", err);
             }
@@ -49,6 +136,8 @@ fn run_client(ui_message_sender: Option<glib::Sender<UIMessage>>) {
     for torrent_handle in torrent_handles {
         torrent_handle.join().unwrap();
     }
+    announce_scheduler_sender.stop();
+    announce_scheduler_handle.join().unwrap();
 
     info!("Finished running");
 }
@@ -37,6 +37,7 @@ fn build_metainfo(
     let name_key = b"name";
     let length_key = b"length";
     let announce_key = b"announce";
+    let announce_list_key = b"announce-list";
     let files_key = b"files";
     let path_key = b"path";
 
@@ -94,6 +95,10 @@ fn build_metainfo(
         info,
         info_hash: get_hash(hashmap, info_key),
         announce: bencode_decoded_bytes_to_string(hashmap, announce_key)?,
+        announce_list: match get_from_bencoded_values_hashmap(hashmap, announce_list_key) {
+            Ok(announce_list) => bencode_list_to_tiers(&announce_list)?,
+            Err(_) => vec![],
+        },
     };
     validate(&metainfo)?;
     Ok(metainfo)
@@ -113,6 +118,23 @@ fn bencode_list_to_string_path(list: &BencodeDecodedValue) -> Result<String, Ben
     Ok(path)
 }
 
+// Converts the announce-list, a list of tiers that are lists of tracker urls
+fn bencode_list_to_tiers(
+    list: &BencodeDecodedValue,
+) -> Result<Vec<Vec<String>>, BencodeDecoderError> {
+    let mut tiers = Vec::new();
+    for tier in list.get_as_list()? {
+        let mut urls = Vec::new();
+        for url in tier.get_as_list()? {
+            urls.push(String::from_utf8_lossy(url.get_as_string()?).to_string());
+        }
+        if !urls.is_empty() {
+            tiers.push(urls);
+        }
+    }
+    Ok(tiers)
+}
+
 // Converts the vector of pieces into a vector of each piece hash
 // each index represent each piece of file
 fn get_vec_of_hashes(pieces: &[u8]) -> Vec<Vec<u8>> {
@@ -235,6 +257,7 @@ mod tests {
             info: expected_info,
             info_hash: decode_hex("d0d14c926e6e99761a2fdcff27b403d96376eff6").unwrap(),
             announce: "udp://tracker.openbittorrent.com:80".to_string(),
+            announce_list: vec![],
         };
 
         assert_eq!(metainfo, expected_metainfo);
@@ -247,6 +270,19 @@ mod tests {
         assert!(matches!(metainfo_result, Ok(_)));
     }
 
+    #[test]
+    fn parses_announce_list_tiers() {
+        let test_bytes: Vec<u8> = std::fs::read("example_torrents/ubuntu.torrent").unwrap();
+        let metainfo = parse(&test_bytes).unwrap();
+        assert_eq!(
+            metainfo.announce_list,
+            vec![
+                vec!["https://torrent.ubuntu.com/announce".to_string()],
+                vec!["https://ipv6.torrent.ubuntu.com/announce".to_string()],
+            ]
+        );
+    }
+
     #[test]
     fn empty_byte_array() {
         let empty_bytes: Vec<u8> = Vec::new();
@@ -301,6 +337,7 @@ mod tests {
             info: invalid_info,
             info_hash: decode_hex("d0d14c926e6e99761a2fdcff27b403d96376eff6").unwrap(),
             announce: "udp://tracker.openbittorrent.com:80".to_string(),
+            announce_list: vec![],
         };
 
         assert!(matches!(
@@ -15,6 +15,8 @@ pub struct Metainfo {
     pub info_hash: Vec<u8>,
     ///the announce URL used for connecting to the tracker
     pub announce: String,
+    ///tiers of backup tracker URLs (BEP 12), empty if the torrent has no announce-list
+    pub announce_list: Vec<Vec<String>>,
 }
 #[derive(Debug, Clone)]
 ///Bencode-Decoded Info Dictionary of a metainfo file.
@@ -81,5 +83,6 @@ impl PartialEq for Metainfo {
         self.info == other.info
             && self.info_hash == other.info_hash
             && self.announce == other.announce
+            && self.announce_list == other.announce_list
     }
 }
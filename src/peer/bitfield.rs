@@ -0,0 +1,231 @@
+const WORD_BITS: usize = 64;
+
+/// Pieces a peer has, packed in u64 words. Piece 0 is the most significant bit of the first
+/// word, following the byte order of the bitfield message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitfield {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Default for Bitfield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bitfield {
+    pub fn new() -> Self {
+        Bitfield {
+            words: vec![],
+            len: 0,
+        }
+    }
+
+    /// Creates a bitfield for `len` pieces, none of them set
+    pub fn with_len(len: usize) -> Self {
+        Bitfield {
+            words: vec![0; len.div_ceil(WORD_BITS)],
+            len,
+        }
+    }
+
+    pub fn non_empty(&self) -> bool {
+        self.len != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Amount of pieces the bitfield has room for
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Replaces the content with the payload of a bitfield message. A bitfield created with
+    /// `with_len` keeps its length and drops the spare bits the message pads its last byte with
+    pub fn set_bitfield(&mut self, bitfield: &[u8]) {
+        if self.len == 0 {
+            self.len = bitfield.len() * 8;
+        }
+        self.words = bitfield
+            .chunks(WORD_BITS / 8)
+            .map(|chunk| {
+                let mut bytes = [0u8; WORD_BITS / 8];
+                bytes[..chunk.len()].copy_from_slice(chunk);
+                u64::from_be_bytes(bytes)
+            })
+            .collect();
+        self.words.resize(self.len.div_ceil(WORD_BITS), 0);
+        self.clear_spare_bits();
+    }
+
+    pub fn has_piece(&self, index: usize) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        self.words[index / WORD_BITS] & Self::mask(index) != 0
+    }
+
+    pub fn set_piece(&mut self, index: usize) {
+        if index < self.len {
+            self.words[index / WORD_BITS] |= Self::mask(index);
+        }
+    }
+
+    pub fn clear_piece(&mut self, index: usize) {
+        if index < self.len {
+            self.words[index / WORD_BITS] &= !Self::mask(index);
+        }
+    }
+
+    /// Whether any piece is set
+    pub fn has_any(&self) -> bool {
+        self.words.iter().any(|word| *word != 0)
+    }
+
+    /// Amount of pieces set
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Pieces set in both bitfields, as long as the shortest of them
+    pub fn and(&self, other: &Bitfield) -> Bitfield {
+        let len = self.len.min(other.len);
+        let words = self
+            .words
+            .iter()
+            .zip(other.words.iter())
+            .map(|(word, other_word)| word & other_word)
+            .collect();
+        let mut bitfield = Bitfield { words, len };
+        bitfield.clear_spare_bits();
+        bitfield
+    }
+
+    /// Pieces set in this bitfield but not in `other`, as in pieces a peer has that we need
+    pub fn and_not(&self, other: &Bitfield) -> Bitfield {
+        let words = self
+            .words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| word & !other.words.get(i).copied().unwrap_or(0))
+            .collect();
+        Bitfield {
+            words,
+            len: self.len,
+        }
+    }
+
+    /// Indexes of the pieces set, in increasing order
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let offset = word.leading_zeros() as usize;
+                    word &= !(1 << (WORD_BITS - 1 - offset));
+                    Some(word_index * WORD_BITS + offset)
+                })
+            })
+            .take_while(move |index| *index < self.len)
+    }
+
+    // Bits of the last word beyond `len` are never counted as pieces
+    fn clear_spare_bits(&mut self) {
+        let used_bits = self.len % WORD_BITS;
+        if let Some(last_word) = self.words.last_mut() {
+            if used_bits != 0 {
+                *last_word &= !0 << (WORD_BITS - used_bits);
+            }
+        }
+    }
+
+    fn mask(index: usize) -> u64 {
+        1 << (WORD_BITS - 1 - index % WORD_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn bitfield_from(pieces: &[bool]) -> Bitfield {
+        let mut bitfield = Bitfield::with_len(pieces.len());
+        for (index, _) in pieces.iter().enumerate().filter(|(_, has)| **has) {
+            bitfield.set_piece(index);
+        }
+        bitfield
+    }
+
+    #[test]
+    fn set_bitfield_reads_message_bit_order() {
+        let mut bitfield = Bitfield::new();
+        bitfield.set_bitfield(&[0b1000_0000, 0, 0, 0, 0, 0, 0, 0, 0b0100_0001]);
+        assert_eq!(bitfield.len(), 72);
+        assert_eq!(bitfield.iter_ones().collect::<Vec<_>>(), vec![0, 65, 71]);
+        assert_eq!(bitfield.count_ones(), 3);
+    }
+
+    #[test]
+    fn set_bitfield_ignores_spare_bits() {
+        let mut bitfield = Bitfield::with_len(10);
+        bitfield.set_bitfield(&[0b1000_0000, 0b0111_1111]);
+        assert_eq!(bitfield.len(), 10);
+        assert_eq!(bitfield.iter_ones().collect::<Vec<_>>(), vec![0, 9]);
+        assert_eq!(bitfield.count_ones(), 2);
+
+        let mut client = Bitfield::with_len(10);
+        client.set_piece(0);
+        client.set_piece(9);
+        assert!(!bitfield.and_not(&client).has_any());
+    }
+
+    proptest! {
+        #[test]
+        fn count_ones_matches_set_pieces(pieces in prop::collection::vec(any::<bool>(), 0..4096)) {
+            let bitfield = bitfield_from(&pieces);
+            prop_assert_eq!(bitfield.count_ones(), pieces.iter().filter(|has| **has).count());
+        }
+
+        #[test]
+        fn and_not_has_pieces_missing_in_other(
+            pieces in prop::collection::vec(any::<(bool, bool)>(), 0..4096),
+        ) {
+            let peer = bitfield_from(&pieces.iter().map(|(peer, _)| *peer).collect::<Vec<_>>());
+            let client = bitfield_from(&pieces.iter().map(|(_, client)| *client).collect::<Vec<_>>());
+            let needed: Vec<usize> = pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, (peer, client))| *peer && !*client)
+                .map(|(index, _)| index)
+                .collect();
+            prop_assert_eq!(peer.and_not(&client).iter_ones().collect::<Vec<_>>(), needed);
+        }
+
+        #[test]
+        fn and_is_as_long_as_the_shortest(
+            pieces in prop::collection::vec(any::<bool>(), 0..4096),
+            other_len in 0usize..4096,
+        ) {
+            let bitfield = bitfield_from(&pieces);
+            let mut other = Bitfield::with_len(other_len);
+            (0..other_len).for_each(|index| other.set_piece(index));
+            let both = bitfield.and(&other);
+            prop_assert_eq!(both.len(), pieces.len().min(other_len));
+            for (index, has_piece) in pieces.iter().enumerate() {
+                prop_assert_eq!(both.has_piece(index), *has_piece && index < other_len);
+            }
+        }
+    }
+}
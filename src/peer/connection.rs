@@ -3,13 +3,13 @@ use super::errors::PeerConnectionError;
 use super::service::*;
 use super::types::*;
 use super::utils::*;
-use super::Peer;
+use super::{Bitfield, Peer};
 use crate::constants::*;
+use crate::event_bus::UIMessageSender;
 use crate::metainfo::Metainfo;
-use crate::ui::UIMessageSender;
 use log::*;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 pub struct PeerConnection {
     pub _am_choking: bool,
@@ -20,6 +20,8 @@ pub struct PeerConnection {
     pub metainfo: Metainfo,
     pub client_peer_id: Vec<u8>,
     pub bitfield: Bitfield,
+    /// pieces the client already has, to know whether the peer has something we need
+    pub have_pieces: Arc<RwLock<Bitfield>>,
     pub peer_id: Vec<u8>,
     pub peer: Peer,
     pub last_download_rate_update: std::time::Instant,
@@ -33,17 +35,19 @@ impl PeerConnection {
         client_peer_id: &[u8],
         metainfo: &Metainfo,
         message_service: Box<dyn IClientPeerMessageService + Send>,
+        have_pieces: Arc<RwLock<Bitfield>>,
         ui_message_sender: UIMessageSender,
     ) -> Self {
         Self {
             _am_choking: true,
-            _am_interested: true,
+            _am_interested: false,
             peer_choking: true,
             _peer_interested: false,
             client_peer_id: client_peer_id.to_vec(),
             metainfo: metainfo.clone(),
             message_service,
             bitfield: Bitfield::new(),
+            have_pieces,
             peer_id: peer.peer_id.clone(),
             last_downloaded_pieces: Arc::new(AtomicUsize::new(0)),
             last_download_rate_update: std::time::Instant::now(),
@@ -78,9 +82,10 @@ impl PeerConnection {
                 self._peer_interested = false;
             }
             PeerMessageId::Bitfield => {
+                self.bitfield = Bitfield::with_len(self.metainfo.get_piece_count() as usize);
                 self.bitfield.set_bitfield(&message.payload);
             }
-            PeerMessageId::Have => {}
+            PeerMessageId::Have => self.received_have(&message.payload)?,
             PeerMessageId::Piece => {}
             _ => {
                 return Err(IPeerMessageServiceError::UnhandledMessage);
@@ -89,26 +94,74 @@ impl PeerConnection {
         Ok(message)
     }
 
+    fn wait_and_update_state(&mut self) -> Result<(), IPeerMessageServiceError> {
+        self.wait_for_message()?;
+        self.ui_message_sender.update_peer_state(
+            self.peer_id.clone(),
+            PeerConnectionState {
+                client: (PeerState {
+                    chocked: self.peer_choking,
+                    interested: self._am_interested,
+                }),
+                peer: (PeerState {
+                    chocked: self._am_choking,
+                    interested: self._peer_interested,
+                }),
+            },
+        );
+        Ok(())
+    }
+
+    fn wait_for_bitfield(&mut self) -> Result<(), IPeerMessageServiceError> {
+        while self.bitfield.is_empty() {
+            self.wait_and_update_state()?;
+        }
+        Ok(())
+    }
+
     fn wait_until_ready(&mut self) -> Result<(), IPeerMessageServiceError> {
-        loop {
-            self.wait_for_message()?;
-            self.ui_message_sender.update_peer_state(
-                self.peer_id.clone(),
-                PeerConnectionState {
-                    client: (PeerState {
-                        chocked: self.peer_choking,
-                        interested: self._am_interested,
-                    }),
-                    peer: (PeerState {
-                        chocked: self._am_choking,
-                        interested: self._peer_interested,
-                    }),
-                },
-            );
+        while self.peer_choking {
+            self.wait_and_update_state()?;
+        }
+        Ok(())
+    }
 
-            if !self.peer_choking && self.bitfield.non_empty() {
-                break;
-            }
+    // The peer is interesting if it has any piece the client does not have yet
+    fn has_pieces_we_need(&self) -> bool {
+        match self.have_pieces.read() {
+            Ok(have_pieces) => self.bitfield.and_not(&have_pieces).has_any(),
+            Err(_) => self.bitfield.has_any(),
+        }
+    }
+
+    fn send_interested(&mut self) -> Result<(), IPeerMessageServiceError> {
+        self.message_service
+            .send_message(&PeerMessage::interested())
+            .map_err(|_| {
+                IPeerMessageServiceError::SendingMessageError(
+                    "Error trying to send interested message".to_string(),
+                )
+            })?;
+        self._am_interested = true;
+        Ok(())
+    }
+
+    // Records the piece in the peer bitfield, a peer we were not interested in may have just
+    // got a piece we need
+    fn received_have(&mut self, payload: &[u8]) -> Result<(), IPeerMessageServiceError> {
+        let piece_index = match payload.get(0..4) {
+            Some(index) => u32::from_be_bytes([index[0], index[1], index[2], index[3]]) as usize,
+            None => return Ok(()),
+        };
+        if self.bitfield.is_empty() {
+            self.bitfield = Bitfield::with_len(self.metainfo.get_piece_count() as usize);
+        }
+        if piece_index >= self.bitfield.len() {
+            return Ok(());
+        }
+        self.bitfield.set_piece(piece_index);
+        if !self._am_interested && self.has_pieces_we_need() {
+            self.send_interested()?;
         }
         Ok(())
     }
@@ -201,13 +254,21 @@ impl PeerConnection {
                 )
             })?;
 
-        self.message_service
-            .send_message(&PeerMessage::interested())
-            .map_err(|_| {
-                IPeerMessageServiceError::SendingMessageError(
-                    "Error trying to send interested message".to_string(),
-                )
-            })?;
+        self.wait_for_bitfield()?;
+        // the connection is kept, the peer may get pieces we need later on
+        if !self.has_pieces_we_need() {
+            debug!("Peer {:?} has no pieces we need", self.peer_id);
+            self.message_service
+                .send_message(&PeerMessage::not_intersted())
+                .map_err(|_| {
+                    IPeerMessageServiceError::SendingMessageError(
+                        "Error trying to send not interested message".to_string(),
+                    )
+                })?;
+            return Ok(());
+        }
+
+        self.send_interested()?;
         self.wait_until_ready()?;
 
         Ok(())
@@ -240,6 +301,7 @@ mod tests {
 
         let metainfo_mock = Metainfo {
             announce: "".to_string(),
+            announce_list: vec![],
             info: Info {
                 piece_length: 8,
                 pieces: get_pieces_hash_from_bytes(&file),
@@ -266,6 +328,7 @@ mod tests {
             &vec![1, 2, 3, 4],
             &metainfo_mock,
             Box::new(peer_message_stream_mock),
+            Arc::new(RwLock::new(Bitfield::with_len(2))),
             UIMessageSender::no_ui(),
         );
 
@@ -286,6 +349,7 @@ mod tests {
 
         let metainfo_mock = Metainfo {
             announce: "".to_string(),
+            announce_list: vec![],
             info: Info {
                 piece_length: 8,
                 pieces: pieces,
@@ -312,6 +376,7 @@ mod tests {
             &vec![1, 2, 3, 4],
             &metainfo_mock,
             Box::new(peer_message_stream_mock),
+            Arc::new(RwLock::new(Bitfield::with_len(2))),
             UIMessageSender::no_ui(),
         );
 
@@ -320,4 +385,95 @@ mod tests {
             Err(PeerConnectionError::PieceRequestingError(_))
         ));
     }
+
+    // Replies with the scripted messages and records the ids of the messages sent
+    struct ScriptedMessageService {
+        messages: Vec<PeerMessage>,
+        sent: Arc<RwLock<Vec<PeerMessageId>>>,
+    }
+
+    impl IPeerMessageService for ScriptedMessageService {
+        fn wait_for_message(&mut self) -> Result<PeerMessage, IPeerMessageServiceError> {
+            if self.messages.is_empty() {
+                return Err(IPeerMessageServiceError::ReceivingMessageError(
+                    "No more messages".to_string(),
+                ));
+            }
+            Ok(self.messages.remove(0))
+        }
+
+        fn send_message(&mut self, message: &PeerMessage) -> Result<(), IPeerMessageServiceError> {
+            self.sent.write().unwrap().push(message.id);
+            Ok(())
+        }
+    }
+
+    impl IClientPeerMessageService for ScriptedMessageService {
+        fn handshake(
+            &mut self,
+            _info_hash: &[u8],
+            _peer_id: &[u8],
+        ) -> Result<(), IPeerMessageServiceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keeps_peer_without_pieces_we_need_and_gets_interested_on_have() {
+        let metainfo_mock = Metainfo {
+            announce: "".to_string(),
+            announce_list: vec![],
+            info: Info {
+                piece_length: 8,
+                pieces: vec![vec![0; 20], vec![0; 20]],
+                length: 16,
+                name: "".to_string(),
+                files: None,
+            },
+            info_hash: vec![],
+        };
+        let peer_mock = Peer {
+            ip: "".to_string(),
+            port: 0,
+            peer_id: vec![],
+            peer_message_service_provider: mock_peer_message_service_provider,
+        };
+        let sent = Arc::new(RwLock::new(vec![]));
+        let message_service = ScriptedMessageService {
+            messages: vec![
+                PeerMessage::bitfield(vec![true, false]),
+                PeerMessage {
+                    id: PeerMessageId::Have,
+                    length: 5,
+                    payload: 1u32.to_be_bytes().to_vec(),
+                },
+            ],
+            sent: sent.clone(),
+        };
+        let mut have_pieces = Bitfield::with_len(2);
+        have_pieces.set_piece(0);
+        let mut peer_connection = PeerConnection::new(
+            peer_mock,
+            &[1, 2, 3, 4],
+            &metainfo_mock,
+            Box::new(message_service),
+            Arc::new(RwLock::new(have_pieces)),
+            UIMessageSender::no_ui(),
+        );
+
+        assert!(peer_connection.open_connection().is_ok());
+        assert!(!peer_connection._am_interested);
+        assert_eq!(
+            *sent.read().unwrap(),
+            vec![PeerMessageId::Unchoke, PeerMessageId::NotInterested]
+        );
+
+        peer_connection.wait_for_message().unwrap();
+        assert!(peer_connection._am_interested);
+        assert!(peer_connection.get_bitfield().has_piece(1));
+        assert_eq!(
+            sent.read().unwrap().last(),
+            Some(&PeerMessageId::Interested)
+        );
+    }
 }
@@ -1,3 +1,4 @@
+mod bitfield;
 mod connection;
 mod constants;
 mod errors;
@@ -6,6 +7,7 @@ mod service;
 mod types;
 mod utils;
 
+pub use bitfield::Bitfield;
 pub use connection::PeerConnection;
 pub use errors::IPeerMessageServiceError;
 pub use errors::PeerConnectionError;
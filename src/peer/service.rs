@@ -1,7 +1,7 @@
 use super::constants::*;
 use super::errors::*;
 use super::types::*;
-use super::utils::is_keep_alive_message;
+use super::utils::{create_handshake_message, is_keep_alive_message};
 use super::IPeerMessageServiceError;
 use crate::boxed_result::BoxedResult;
 use crate::server::payload_from_request_message;
@@ -18,11 +18,15 @@ pub struct PeerMessageService {
 }
 
 impl PeerMessageService {
-    pub fn connect_to_peer(ip: String, port: u16) -> Result<Self, PeerConnectionError> {
+    pub fn connect_to_peer(
+        ip: String,
+        port: u16,
+        connect_timeout: Duration,
+    ) -> Result<Self, PeerConnectionError> {
         trace!("Connecting to peer at IP: {}:{}", ip, port);
         let ipv4addr: SocketAddrV4 = format!("{}:{}", ip, port).parse().unwrap();
         let ipvaddr = SocketAddr::from(ipv4addr);
-        let stream = TcpStream::connect_timeout(&ipvaddr, Duration::from_secs(100))
+        let stream = TcpStream::connect_timeout(&ipvaddr, connect_timeout)
             .map_err(|e| PeerConnectionError::InitialConnectionError(e.to_string()))?;
         stream
             .set_write_timeout(Some(Duration::new(MESSAGE_TIMEOUT, 0)))
@@ -43,16 +47,6 @@ impl PeerMessageService {
         }
     }
 
-    fn create_handshake_message(&self, info_hash: &[u8], peer_id: &[u8]) -> Vec<u8> {
-        let mut handshake_message = Vec::new();
-        handshake_message.extend_from_slice(&[PSTRLEN]);
-        handshake_message.extend_from_slice(b"BitTorrent protocol");
-        handshake_message.extend_from_slice(&[0u8; 8]);
-        handshake_message.extend_from_slice(info_hash);
-        handshake_message.extend_from_slice(peer_id);
-        handshake_message
-    }
-
     fn try_read_exact(&mut self, buf: &mut [u8]) -> BoxedResult<()> {
         self.stream.read_exact(buf)?;
         Ok(())
@@ -140,8 +134,11 @@ impl IPeerMessageService for PeerMessageService {
     fn send_message(&mut self, message: &PeerMessage) -> Result<(), IPeerMessageServiceError> {
         let mut bytes = Vec::with_capacity((message.length + 4) as usize);
         bytes.extend_from_slice(&message.length.to_be_bytes());
-        bytes.extend_from_slice(&(message.id as u8).to_be_bytes());
-        bytes.extend_from_slice(&message.payload);
+        // keep alive messages are only the length prefix, with no id nor payload
+        if !is_keep_alive_message(message.length) {
+            bytes.extend_from_slice(&(message.id as u8).to_be_bytes());
+            bytes.extend_from_slice(&message.payload);
+        }
         self.write_all(&bytes).map_err(|_| {
             IPeerMessageServiceError::SendingMessageError(
                 "Couldn't send message to other peer".to_string(),
@@ -157,7 +154,7 @@ impl IClientPeerMessageService for PeerMessageService {
         info_hash: &[u8],
         peer_id: &[u8],
     ) -> Result<(), IPeerMessageServiceError> {
-        let handshake_message = self.create_handshake_message(info_hash, peer_id);
+        let handshake_message = create_handshake_message(info_hash, peer_id);
         self.write_all(&handshake_message).map_err(|_| {
             IPeerMessageServiceError::SendingMessageError(
                 "Couldn't send handshake message to other peer".to_string(),
@@ -186,7 +183,7 @@ impl IServerPeerMessageService for PeerMessageService {
                 "Couldn't read handshake from other peer".into(),
             )
         })?;
-        let handshake_message = self.create_handshake_message(info_hash, peer_id);
+        let handshake_message = create_handshake_message(info_hash, peer_id);
         self.write_all(&handshake_message).map_err(|_| {
             IPeerMessageServiceError::SendingMessageError(
                 "Couldn't send handshake message to other peer".to_string(),
@@ -376,14 +373,16 @@ impl IServerPeerMessageService for ServerMessageBitfieldMock {
 pub fn peer_message_service_provider(
     ip: String,
     port: u16,
+    connect_timeout: Duration,
 ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError> {
-    let peer_message_service = PeerMessageService::connect_to_peer(ip, port)?;
+    let peer_message_service = PeerMessageService::connect_to_peer(ip, port, connect_timeout)?;
     Ok(Box::new(peer_message_service))
 }
 
 pub fn mock_peer_message_service_provider(
     _ip: String,
     _port: u16,
+    _connect_timeout: Duration,
 ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError> {
     Ok(Box::new(PeerMessageServiceMock {
         counter: 0,
@@ -391,3 +390,88 @@ pub fn mock_peer_message_service_provider(
         block_size: 0,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::net::TcpListener;
+
+    // two message services talking to each other through a loopback tcp connection
+    fn connected_services() -> (PeerMessageService, PeerMessageService) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (
+            PeerMessageService::from_peer_connection(client),
+            PeerMessageService::from_peer_connection(server),
+        )
+    }
+
+    fn arbitrary_message() -> impl Strategy<Value = PeerMessage> {
+        (0u8..=9, prop::collection::vec(any::<u8>(), 0..2048)).prop_map(|(id, payload)| {
+            PeerMessage {
+                id: PeerMessageId::from_u8(id).unwrap(),
+                length: (payload.len() + 1) as u32,
+                payload,
+            }
+        })
+    }
+
+    fn assert_same_message(sent: &PeerMessage, received: &PeerMessage) {
+        assert_eq!(sent.id, received.id);
+        assert_eq!(sent.length, received.length);
+        assert_eq!(sent.payload, received.payload);
+    }
+
+    proptest! {
+        #[test]
+        fn sent_messages_are_received_unchanged(messages in prop::collection::vec(arbitrary_message(), 1..8)) {
+            let (mut sender, mut receiver) = connected_services();
+            for message in &messages {
+                sender.send_message(message).unwrap();
+            }
+            for message in &messages {
+                assert_same_message(message, &receiver.wait_for_message().unwrap());
+            }
+        }
+
+        #[test]
+        fn keep_alives_are_skipped_by_receiver(keep_alives in 0usize..5, message in arbitrary_message()) {
+            let (mut sender, mut receiver) = connected_services();
+            for _ in 0..keep_alives {
+                sender.send_message(&PeerMessage::keep_alive()).unwrap();
+            }
+            sender.send_message(&message).unwrap();
+            assert_same_message(&message, &receiver.wait_for_message().unwrap());
+        }
+
+        #[test]
+        fn request_and_piece_messages_survive_the_wire(index in any::<u32>(), begin in any::<u32>(), block in prop::collection::vec(any::<u8>(), 1..1024)) {
+            let (mut sender, mut receiver) = connected_services();
+            let request = PeerMessage::request(index, begin, block.len() as u32);
+            let piece = PeerMessage::piece(index as usize, begin as usize, block);
+            sender.send_message(&request).unwrap();
+            sender.send_message(&piece).unwrap();
+            assert_same_message(&request, &receiver.wait_for_message().unwrap());
+            assert_same_message(&piece, &receiver.wait_for_message().unwrap());
+        }
+    }
+
+    #[test]
+    fn messages_without_payload_are_received_unchanged() {
+        let (mut sender, mut receiver) = connected_services();
+        let messages = vec![
+            PeerMessage::choke(),
+            PeerMessage::unchoke(),
+            PeerMessage::interested(),
+            PeerMessage::not_intersted(),
+        ];
+        for message in &messages {
+            sender.send_message(message).unwrap();
+        }
+        for message in &messages {
+            assert_same_message(message, &receiver.wait_for_message().unwrap());
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use super::errors::*;
 use super::service::*;
-use super::utils::bitmap_from_pieces_vector;
+use super::utils::{bitmap_from_pieces_vector, is_local_address};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct PeerState {
@@ -13,77 +14,52 @@ pub struct PeerConnectionState {
     pub peer: PeerState,
 }
 
-#[derive(Clone, Debug)]
-pub struct Bitfield(Vec<u8>);
-
-impl Default for Bitfield {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl Bitfield {
-    pub fn new() -> Self {
-        Bitfield(vec![])
-    }
-
-    pub fn non_empty(&self) -> bool {
-        !self.0.is_empty()
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
-
-    pub fn len(&self) -> usize {
-        self.0.len()
-    }
-
-    pub fn iter(&self) -> std::slice::Iter<u8> {
-        self.0.iter()
-    }
-
-    pub fn set_bitfield(&mut self, bitfield: &[u8]) {
-        self.0 = bitfield.to_vec();
-    }
-
-    pub fn has_piece(&self, index: usize) -> bool {
-        let byte_index = index / 8;
-        let offset = index % 8;
-        if byte_index >= self.0.len() {
-            return false;
-        }
-        (self.0[byte_index] >> (7 - offset) & 1) != 0
-    }
-
-    fn _set_piece(&mut self, index: usize) {
-        let byte_index = index / 8;
-        let offset = index % 8;
-
-        if byte_index >= self.0.len() {
-            return;
-        }
-        self.0[byte_index] |= 1 << (7 - offset);
-    }
-}
+/// Opens the connection with a peer, which decides the transport used to talk to it
+pub type PeerMessageServiceProvider =
+    fn(
+        ip: String,
+        port: u16,
+        connect_timeout: Duration,
+    ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Peer {
     pub ip: String,
     pub port: u16,
     pub peer_id: Vec<u8>,
-    pub peer_message_service_provider:
-        fn(
-            ip: String,
-            port: u16,
-        ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError>,
+    pub peer_message_service_provider: PeerMessageServiceProvider,
 }
 
 impl Peer {
     pub fn connect(
         &self,
+        dial_timeouts: &DialTimeouts,
     ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError> {
-        (self.peer_message_service_provider)(self.ip.clone(), self.port)
+        (self.peer_message_service_provider)(
+            self.ip.clone(),
+            self.port,
+            dial_timeouts.for_ip(&self.ip),
+        )
+    }
+}
+
+/// Connect timeouts used when dialing peers, so unreachable peers in the local network
+/// fail fast instead of holding a dialing round as long as a slow peer on the internet
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DialTimeouts {
+    /// for private (RFC1918), link-local and loopback addresses
+    pub lan: Duration,
+    /// for every other address
+    pub wan: Duration,
+}
+
+impl DialTimeouts {
+    pub fn for_ip(&self, ip: &str) -> Duration {
+        if is_local_address(ip) {
+            self.lan
+        } else {
+            self.wan
+        }
     }
 }
 
@@ -184,7 +160,7 @@ impl PeerMessage {
 
     pub fn keep_alive() -> PeerMessage {
         PeerMessage {
-            id: PeerMessageId::Choke,
+            id: PeerMessageId::KeepAlive,
             length: 0,
             payload: vec![],
         }
@@ -202,7 +178,7 @@ impl PeerMessage {
     pub fn not_intersted() -> PeerMessage {
         PeerMessage {
             id: PeerMessageId::NotInterested,
-            length: 0,
+            length: 1,
             payload: vec![],
         }
     }
@@ -210,8 +186,63 @@ impl PeerMessage {
     pub fn choke() -> PeerMessage {
         PeerMessage {
             id: PeerMessageId::Choke,
-            length: 0,
+            length: 1,
             payload: vec![],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer::utils::vec_be_to_u32;
+    use crate::peer::Bitfield;
+    use proptest::prelude::*;
+
+    fn bitfield_from(pieces: &[bool]) -> Bitfield {
+        let mut bitfield = Bitfield::new();
+        bitfield.set_bitfield(&bitmap_from_pieces_vector(pieces));
+        bitfield
+    }
+
+    proptest! {
+        #[test]
+        fn bitfield_has_exactly_the_pieces_it_was_built_from(pieces in prop::collection::vec(any::<bool>(), 0..4096)) {
+            let bitfield = bitfield_from(&pieces);
+            for (index, has_piece) in pieces.iter().enumerate() {
+                prop_assert_eq!(bitfield.has_piece(index), *has_piece);
+            }
+        }
+
+        #[test]
+        fn bitfield_never_has_pieces_past_its_end(pieces in prop::collection::vec(any::<bool>(), 0..4096), offset in 0usize..1024) {
+            let bitfield = bitfield_from(&pieces);
+            prop_assert!(!bitfield.has_piece(pieces.len() + offset));
+        }
+
+        #[test]
+        fn bitfield_message_length_counts_id_and_spare_bits(pieces in prop::collection::vec(any::<bool>(), 0..4096)) {
+            let message = PeerMessage::bitfield(pieces.clone());
+            prop_assert_eq!(message.payload.len(), pieces.len().div_ceil(8));
+            prop_assert_eq!(message.length as usize, message.payload.len() + 1);
+        }
+
+        #[test]
+        fn request_payload_is_big_endian(index in any::<u32>(), begin in any::<u32>(), length in any::<u32>()) {
+            let message = PeerMessage::request(index, begin, length);
+            prop_assert_eq!(message.length, 13);
+            prop_assert_eq!(vec_be_to_u32(&message.payload[0..4]), index);
+            prop_assert_eq!(vec_be_to_u32(&message.payload[4..8]), begin);
+            prop_assert_eq!(vec_be_to_u32(&message.payload[8..12]), length);
+        }
+
+        #[test]
+        fn piece_payload_has_header_and_block(index in any::<u32>(), offset in any::<u32>(), block in prop::collection::vec(any::<u8>(), 0..1024)) {
+            let message = PeerMessage::piece(index as usize, offset as usize, block.clone());
+            prop_assert_eq!(message.length as usize, block.len() + 9);
+            prop_assert_eq!(vec_be_to_u32(&message.payload[0..4]), index);
+            prop_assert_eq!(vec_be_to_u32(&message.payload[4..8]), offset);
+            prop_assert_eq!(&message.payload[8..], &block[..]);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use super::constants::*;
 use crate::metainfo::Metainfo;
 use sha1::{Digest, Sha1};
+use std::net::IpAddr;
 
 // Gets sha1 hash of vector u8
 pub fn sha1_of(vec: &[u8]) -> Vec<u8> {
@@ -83,10 +84,99 @@ pub fn bitmap_from_pieces_vector(vector: &[bool]) -> Vec<u8> {
     bitmap
 }
 
+// whether the ip belongs to the local network: private (RFC1918), link-local or loopback.
+// Anything that is not an ip address, like a hostname, is considered remote
+pub fn is_local_address(ip: &str) -> bool {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        Ok(IpAddr::V6(ip)) => {
+            let first_segment = ip.segments()[0];
+            ip.is_loopback()
+                // unique local fc00::/7
+                || (first_segment & 0xfe00) == 0xfc00
+                // link-local fe80::/10
+                || (first_segment & 0xffc0) == 0xfe80
+                || ip
+                    .to_ipv4_mapped()
+                    .map(|ip| ip.is_private() || ip.is_link_local() || ip.is_loopback())
+                    .unwrap_or(false)
+        }
+        Err(_) => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
 
     use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn vec_be_to_u32_reads_big_endian_bytes(num in any::<u32>()) {
+            prop_assert_eq!(vec_be_to_u32(&num.to_be_bytes()), num);
+        }
+
+        #[test]
+        fn handshake_has_protocol_info_hash_and_peer_id_in_place(info_hash in any::<[u8; 20]>(), peer_id in any::<[u8; 20]>()) {
+            let handshake = create_handshake_message(&info_hash, &peer_id);
+            prop_assert_eq!(handshake.len(), HANDSHAKE_LENGTH);
+            prop_assert_eq!(handshake[0], PSTRLEN);
+            prop_assert_eq!(&handshake[1..20], b"BitTorrent protocol");
+            prop_assert_eq!(&handshake[20..28], &[0u8; 8]);
+            prop_assert_eq!(&handshake[28..48], &info_hash);
+            prop_assert_eq!(&handshake[48..68], &peer_id);
+        }
+
+        #[test]
+        fn bitmap_spare_bits_are_cleared(pieces in prop::collection::vec(Just(true), 1..64)) {
+            let bitmap = bitmap_from_pieces_vector(&pieces);
+            let spare_bits = bitmap.len() * 8 - pieces.len();
+            let last_byte = bitmap[bitmap.len() - 1];
+            prop_assert_eq!(last_byte.count_ones() as usize, 8 - spare_bits);
+            prop_assert_eq!(last_byte.trailing_zeros() as usize, spare_bits);
+        }
+
+        #[test]
+        fn valid_block_accepts_only_requested_piece_and_offset(index in any::<u32>(), offset in any::<u32>(), other in any::<u32>()) {
+            let mut payload = index.to_be_bytes().to_vec();
+            payload.extend_from_slice(&offset.to_be_bytes());
+            prop_assert!(valid_block(&payload, index, offset));
+            prop_assert_eq!(valid_block(&payload, other, offset), other == index);
+            prop_assert_eq!(valid_block(&payload, index, other), other == offset);
+        }
+    }
+
+    #[test]
+    fn private_and_link_local_addresses_are_local() {
+        for ip in [
+            "10.0.0.1",
+            "172.16.5.4",
+            "172.31.255.255",
+            "192.168.1.20",
+            "169.254.10.10",
+            "127.0.0.1",
+            "fe80::1",
+            "fd12:3456::1",
+            "::1",
+            "::ffff:192.168.0.1",
+        ] {
+            assert!(is_local_address(ip), "{} should be local", ip);
+        }
+    }
+
+    #[test]
+    fn public_addresses_and_hostnames_are_not_local() {
+        for ip in [
+            "8.8.8.8",
+            "172.32.0.1",
+            "192.169.0.1",
+            "2001:db8::1",
+            "tracker.example.com",
+        ] {
+            assert!(!is_local_address(ip), "{} should not be local", ip);
+        }
+    }
 
     #[test]
     fn create_bitmap_from_vector_of_booleans_only_last_piece_is_present() {
@@ -0,0 +1,278 @@
+use crate::peer::Peer;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+type PeerAddress = (String, u16);
+
+/// A peer waiting to be dialed
+pub struct Candidate {
+    pub peer: Peer,
+    /// when the peer was first received from the tracker
+    pub first_seen: Instant,
+    pub failed_dials: u32,
+}
+
+/// Ages of the candidates left in the pool after pruning it
+#[derive(Debug, PartialEq, Eq)]
+pub struct CandidateAging {
+    pub remaining: usize,
+    /// candidates dropped because they were older than the TTL
+    pub expired: usize,
+    /// candidates dropped after too many failed dials since the last pruning
+    pub exhausted: usize,
+    pub oldest: Duration,
+    pub average: Duration,
+}
+
+impl fmt::Display for CandidateAging {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} candidates (oldest {}s, average {}s), {} expired, {} dropped after failed dials",
+            self.remaining,
+            self.oldest.as_secs(),
+            self.average.as_secs(),
+            self.expired,
+            self.exhausted
+        )
+    }
+}
+
+/// Peers received from the tracker which we are not connected to yet.
+///
+/// Peers are ingested one by one: the ones already known are skipped and, once
+/// `capacity` candidates are waiting, the rest of the list is dropped, so a
+/// tracker answering with hundreds of peers does not translate into hundreds of dials.
+///
+/// Candidates that fail to connect go back to the pool until they fail `max_failed_dials`
+/// times, and every candidate older than `ttl` is dropped when the pool is pruned.
+pub struct CandidatePool {
+    candidates: VecDeque<Candidate>,
+    addresses: HashSet<PeerAddress>,
+    capacity: usize,
+    ttl: Duration,
+    max_failed_dials: u32,
+    exhausted: usize,
+}
+
+impl CandidatePool {
+    pub fn new(capacity: usize, ttl: Duration, max_failed_dials: u32) -> Self {
+        CandidatePool {
+            candidates: VecDeque::new(),
+            addresses: HashSet::new(),
+            capacity,
+            ttl,
+            max_failed_dials,
+            exhausted: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Adds to the pool the peers that are neither waiting in it nor known by `is_known`.
+    /// Returns how many peers were added
+    pub fn ingest(
+        &mut self,
+        peers: impl IntoIterator<Item = Peer>,
+        is_known: impl Fn(&Peer) -> bool,
+    ) -> usize {
+        let mut added = 0;
+        for peer in peers {
+            if self.candidates.len() >= self.capacity {
+                break;
+            }
+            if is_known(&peer) || !self.addresses.insert(peer_address(&peer)) {
+                continue;
+            }
+            self.candidates.push_back(Candidate {
+                peer,
+                first_seen: Instant::now(),
+                failed_dials: 0,
+            });
+            added += 1;
+        }
+        added
+    }
+
+    /// Takes at most `size` candidates out of the pool, oldest first
+    pub fn next_batch(&mut self, size: usize) -> Vec<Candidate> {
+        let size = size.min(self.candidates.len());
+        let batch: Vec<Candidate> = self.candidates.drain(..size).collect();
+        for candidate in &batch {
+            self.addresses.remove(&peer_address(&candidate.peer));
+        }
+        batch
+    }
+
+    /// Puts back a candidate we could not connect to, unless it already failed too many times
+    /// or the same peer was ingested again in the meantime
+    pub fn failed_dial(&mut self, mut candidate: Candidate) {
+        candidate.failed_dials += 1;
+        if candidate.failed_dials >= self.max_failed_dials {
+            self.exhausted += 1;
+            return;
+        }
+        if self.addresses.insert(peer_address(&candidate.peer)) {
+            self.candidates.push_back(candidate);
+        }
+    }
+
+    /// Drops the candidates older than the TTL and returns the aging of the pool
+    pub fn prune(&mut self) -> CandidateAging {
+        let now = Instant::now();
+        let ttl = self.ttl;
+        let before = self.candidates.len();
+        let addresses = &mut self.addresses;
+        self.candidates.retain(|candidate| {
+            let alive = now.duration_since(candidate.first_seen) < ttl;
+            if !alive {
+                addresses.remove(&peer_address(&candidate.peer));
+            }
+            alive
+        });
+
+        let ages: Vec<Duration> = self
+            .candidates
+            .iter()
+            .map(|candidate| now.duration_since(candidate.first_seen))
+            .collect();
+        let aging = CandidateAging {
+            remaining: ages.len(),
+            expired: before - ages.len(),
+            exhausted: self.exhausted,
+            oldest: ages.iter().max().copied().unwrap_or_default(),
+            average: match ages.len() {
+                0 => Duration::ZERO,
+                count => ages.iter().sum::<Duration>() / count as u32,
+            },
+        };
+        self.exhausted = 0;
+        aging
+    }
+}
+
+pub fn peer_address(peer: &Peer) -> PeerAddress {
+    (peer.ip.clone(), peer.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer::mock_peer_message_service_provider;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn peer_at(ip: &str, port: u16) -> Peer {
+        Peer {
+            ip: ip.to_string(),
+            port,
+            peer_id: vec![port as u8],
+            peer_message_service_provider: mock_peer_message_service_provider,
+        }
+    }
+
+    #[test]
+    fn ingest_skips_repeated_and_known_peers() {
+        let mut pool = CandidatePool::new(10, TTL, 3);
+        let peers = vec![
+            peer_at("1.1.1.1", 1),
+            peer_at("1.1.1.1", 1),
+            peer_at("1.1.1.1", 2),
+            peer_at("2.2.2.2", 1),
+        ];
+
+        let added = pool.ingest(peers, |peer| peer.ip == "2.2.2.2");
+
+        assert_eq!(added, 2);
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn ingest_stops_when_pool_is_full() {
+        let mut pool = CandidatePool::new(3, TTL, 3);
+        let peers = (0..100).map(|port| peer_at("1.1.1.1", port));
+
+        let added = pool.ingest(peers, |_| false);
+
+        assert_eq!(added, 3);
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[test]
+    fn batches_are_taken_in_arrival_order() {
+        let mut pool = CandidatePool::new(10, TTL, 3);
+        pool.ingest((0..5).map(|port| peer_at("1.1.1.1", port)), |_| false);
+
+        let first = pool.next_batch(2);
+        let second = pool.next_batch(10);
+
+        assert_eq!(
+            first.iter().map(|c| c.peer.port).collect::<Vec<u16>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            second.iter().map(|c| c.peer.port).collect::<Vec<u16>>(),
+            vec![2, 3, 4]
+        );
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn dialed_peers_can_be_ingested_again() {
+        let mut pool = CandidatePool::new(10, TTL, 3);
+        pool.ingest(vec![peer_at("1.1.1.1", 1)], |_| false);
+        pool.next_batch(1);
+
+        assert_eq!(pool.ingest(vec![peer_at("1.1.1.1", 1)], |_| false), 1);
+    }
+
+    #[test]
+    fn candidates_are_dropped_after_max_failed_dials() {
+        let mut pool = CandidatePool::new(10, TTL, 2);
+        pool.ingest(vec![peer_at("1.1.1.1", 1)], |_| false);
+
+        let candidate = pool.next_batch(1).pop().unwrap();
+        pool.failed_dial(candidate);
+        assert_eq!(pool.len(), 1);
+
+        let candidate = pool.next_batch(1).pop().unwrap();
+        assert_eq!(candidate.failed_dials, 1);
+        pool.failed_dial(candidate);
+        assert!(pool.is_empty());
+        assert_eq!(pool.prune().exhausted, 1);
+        assert_eq!(pool.prune().exhausted, 0);
+    }
+
+    #[test]
+    fn prune_drops_expired_candidates() {
+        let mut pool = CandidatePool::new(10, Duration::ZERO, 3);
+        pool.ingest((0..4).map(|port| peer_at("1.1.1.1", port)), |_| false);
+
+        let aging = pool.prune();
+
+        assert_eq!(aging.expired, 4);
+        assert_eq!(aging.remaining, 0);
+        assert!(pool.is_empty());
+        assert_eq!(pool.ingest(vec![peer_at("1.1.1.1", 0)], |_| false), 1);
+    }
+
+    #[test]
+    fn prune_keeps_fresh_candidates() {
+        let mut pool = CandidatePool::new(10, TTL, 3);
+        pool.ingest((0..4).map(|port| peer_at("1.1.1.1", port)), |_| false);
+
+        let aging = pool.prune();
+
+        assert_eq!(aging.expired, 0);
+        assert_eq!(aging.remaining, 4);
+        assert!(aging.oldest < TTL);
+    }
+}
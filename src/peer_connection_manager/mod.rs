@@ -1,8 +1,10 @@
+mod candidate_pool;
 mod open_peer_connection;
 pub mod sender;
 pub mod types;
 pub mod worker;
 
+pub use candidate_pool::CandidatePool;
 pub use open_peer_connection::*;
 pub use sender::PeerConnectionManagerSender;
 pub use types::*;
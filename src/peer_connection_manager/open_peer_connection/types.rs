@@ -1,13 +1,14 @@
 use super::errors::OpenPeerConnectionError;
 use super::sender::*;
 use super::worker::*;
+use crate::event_bus::UIMessageSender;
 use crate::metainfo::Metainfo;
 use crate::peer::*;
 use crate::peer_connection_manager::PeerConnectionManagerSender;
 use crate::piece_manager::sender::PieceManagerSender;
 use crate::piece_saver::sender::PieceSaverSender;
-use crate::ui::UIMessageSender;
 use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
 
 #[derive(Debug, Clone)]
 pub enum OpenPeerConnectionMessage {
@@ -21,6 +22,7 @@ pub enum OpenPeerConnectionMessage {
 
 //Creates Sender and Worker for OpenPeerConnection. Opens connection with received peer
 //before returning.
+#[allow(clippy::too_many_arguments)]
 pub fn new_open_peer_connection(
     peer: Peer,
     piece_manager_sender: PieceManagerSender,
@@ -28,14 +30,17 @@ pub fn new_open_peer_connection(
     peer_connection_manager_sender: PeerConnectionManagerSender,
     metainfo: &Metainfo,
     client_peer_id: &[u8],
+    dial_timeouts: &DialTimeouts,
+    have_pieces: Arc<RwLock<Bitfield>>,
     ui_message_sender: UIMessageSender,
 ) -> Result<(OpenPeerConnectionSender, OpenPeerConnectionWorker), OpenPeerConnectionError> {
-    let peer_message_stream = peer.connect()?;
+    let peer_message_stream = peer.connect(dial_timeouts)?;
     let mut connection = PeerConnection::new(
         peer,
         client_peer_id,
         metainfo,
         peer_message_stream,
+        have_pieces,
         ui_message_sender,
     );
     connection.open_connection()?;
@@ -9,7 +9,7 @@ use log::*;
 use std::sync::mpsc::Receiver;
 const MIN_FAILED_CONNECTIONS: u32 = 1;
 const LOGGER: CustomLogger = CustomLogger::init("Open Peer Connection");
-use crate::ui::PeerStatistics;
+use crate::event_bus::PeerStatistics;
 pub struct OpenPeerConnectionWorker {
     pub receiver: Receiver<OpenPeerConnectionMessage>,
     pub connection: PeerConnection,
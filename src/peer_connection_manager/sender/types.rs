@@ -28,4 +28,10 @@ impl PeerConnectionManagerSender {
             .sender
             .send(PeerConnectionManagerMessage::FailedConnection(peer_id));
     }
+
+    pub fn finished_dialing(&self) {
+        let _ = self
+            .sender
+            .send(PeerConnectionManagerMessage::FinishedDialing);
+    }
 }
@@ -1,17 +1,23 @@
+use super::candidate_pool::CandidatePool;
 use super::sender::*;
+use super::worker::types::MAX_CANDIDATES;
 use super::worker::*;
+use crate::config::Config;
+use crate::event_bus::UIMessageSender;
 use crate::metainfo::Metainfo;
+use crate::peer::Bitfield;
 use crate::piece_manager::sender::PieceManagerSender;
 use crate::piece_saver::sender::PieceSaverSender;
-use crate::ui::UIMessageSender;
 use std::collections::HashMap;
 use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
 use std::time::Instant;
 
 #[derive(Debug)]
 pub enum PeerConnectionManagerMessage {
     DownloadPiece(Vec<u8>, u32),
     FailedConnection(Vec<u8>),
+    FinishedDialing,
     CloseConnections,
 }
 
@@ -20,6 +26,8 @@ pub fn new_peer_connection_manager(
     piece_saver_sender: PieceSaverSender,
     metainfo: &Metainfo,
     client_peer_id: &[u8],
+    config: &Config,
+    have_pieces: Arc<RwLock<Bitfield>>,
     ui_message_sender: UIMessageSender,
 ) -> (PeerConnectionManagerSender, PeerConnectionManagerWorker) {
     let (tx, rx) = mpsc::channel();
@@ -34,6 +42,18 @@ pub fn new_peer_connection_manager(
             client_peer_id: client_peer_id.to_vec(),
             ui_message_sender,
             last_announce: Instant::now(),
+            candidate_pool: CandidatePool::new(
+                MAX_CANDIDATES,
+                config.candidate_ttl,
+                config.candidate_max_failed_dials,
+            ),
+            dial_timeouts: config.dial_timeouts,
+            have_pieces,
+            dialing: None,
+            dial_round_running: false,
+            candidates_to_dial: 0,
+            dialed_connections: 0,
+            deferred_messages: Vec::new(),
         },
     )
 }
@@ -1,17 +1,20 @@
+use crate::event_bus::UIMessageSender;
 use crate::logger::CustomLogger;
 use crate::metainfo::Metainfo;
 use crate::peer::*;
+use crate::peer_connection_manager::candidate_pool::{peer_address, Candidate, CandidatePool};
 use crate::peer_connection_manager::types::PeerConnectionManagerMessage;
 use crate::peer_connection_manager::{open_peer_connection::*, PeerConnectionManagerSender};
 use crate::piece_manager::sender::PieceManagerSender;
 use crate::piece_saver::sender::PieceSaverSender;
 use crate::tracker::ITrackerService;
-use crate::ui::UIMessageSender;
 use log::*;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::mpsc::{Receiver, RecvError};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::RwLock;
 use std::thread::JoinHandle;
 use std::time::Duration;
 use std::time::Instant;
@@ -21,6 +24,30 @@ const LOGGER: CustomLogger = CustomLogger::init("Peer Connection Manager");
 pub const FIRST_MIN_CONNECTIONS: usize = 2;
 pub const MAX_TRACKER_REQUESTS: u32 = 3;
 pub const MIN_CONNECTIONS: usize = 10;
+/// Maximum amount of open connections with peers
+pub const MAX_CONNECTIONS: usize = 30;
+/// Amount of peers dialed at the same time
+pub const DIAL_BATCH_SIZE: usize = 10;
+/// Maximum amount of peers waiting to be dialed
+pub const MAX_CANDIDATES: usize = 200;
+
+/// Candidates dialed in a batch and the connections that could be opened with them
+pub struct DialedBatch {
+    candidates: Vec<Candidate>,
+    connections: HashMap<Vec<u8>, PeerConnection>,
+}
+
+// Everything a dialing thread needs to open connections without the worker
+#[derive(Clone)]
+struct DialContext {
+    piece_manager_sender: PieceManagerSender,
+    piece_saver_sender: PieceSaverSender,
+    metainfo: Metainfo,
+    client_peer_id: Vec<u8>,
+    dial_timeouts: DialTimeouts,
+    have_pieces: Arc<RwLock<Bitfield>>,
+    ui_message_sender: UIMessageSender,
+}
 
 #[derive(Debug)]
 pub struct PeerConnection {
@@ -40,9 +67,24 @@ pub struct PeerConnectionManagerWorker {
     pub client_peer_id: Vec<u8>,
     pub ui_message_sender: UIMessageSender,
     pub last_announce: Instant,
+    pub candidate_pool: CandidatePool,
+    pub dial_timeouts: DialTimeouts,
+    /// pieces the client already has, shared with the piece manager
+    pub have_pieces: Arc<RwLock<Bitfield>>,
+    /// batch being dialed in the background, while the worker keeps handling messages
+    pub dialing: Option<JoinHandle<DialedBatch>>,
+    /// whether a dialing round is running, so a new one waits until the piece manager is told
+    /// the current one finished
+    pub dial_round_running: bool,
+    /// candidates left to dial in the current round, and connections opened in it
+    pub candidates_to_dial: usize,
+    pub dialed_connections: usize,
+    /// messages about peers of the batch being dialed, handled once the batch is added
+    pub deferred_messages: Vec<PeerConnectionManagerMessage>,
 }
 
 impl PeerConnectionManagerWorker {
+    #[allow(clippy::too_many_arguments)]
     fn open_connection_from_peer(
         peer: Peer,
         piece_manager_sender: PieceManagerSender,
@@ -50,6 +92,8 @@ impl PeerConnectionManagerWorker {
         peer_connection_manager_sender: PeerConnectionManagerSender,
         metainfo: Metainfo,
         client_peer_id: &[u8],
+        dial_timeouts: &DialTimeouts,
+        have_pieces: Arc<RwLock<Bitfield>>,
         ui_message_sender: UIMessageSender,
     ) -> Result<(OpenPeerConnectionSender, JoinHandle<()>), OpenPeerConnectionError> {
         let (open_peer_connection_sender, mut open_peer_connection_worker) =
@@ -60,6 +104,8 @@ impl PeerConnectionManagerWorker {
                 peer_connection_manager_sender,
                 &metainfo,
                 client_peer_id,
+                dial_timeouts,
+                have_pieces,
                 ui_message_sender,
             )?;
 
@@ -73,7 +119,7 @@ impl PeerConnectionManagerWorker {
         Ok((open_peer_connection_sender, handle))
     }
 
-    fn _open_peer_connection_count(&self) -> usize {
+    fn open_peer_connection_count(&self) -> usize {
         self.peer_connections
             .values()
             .filter(|peer_connection| peer_connection.is_open)
@@ -86,23 +132,36 @@ impl PeerConnectionManagerWorker {
         }
     }
 
-    pub fn start_peer_connections(
-        &mut self,
+    fn dial_context(&self) -> DialContext {
+        DialContext {
+            piece_manager_sender: self.piece_manager_sender.clone(),
+            piece_saver_sender: self.piece_saver_sender.clone(),
+            metainfo: self.metainfo.clone(),
+            client_peer_id: self.client_peer_id.clone(),
+            dial_timeouts: self.dial_timeouts,
+            have_pieces: self.have_pieces.clone(),
+            ui_message_sender: self.ui_message_sender.clone(),
+        }
+    }
+
+    // Dials every peer at the same time and returns the connections that could be opened
+    fn connect_to_peers(
+        context: &DialContext,
         peers: Vec<Peer>,
-        peer_connection_manager_sender: PeerConnectionManagerSender,
-    ) {
-        LOGGER.info(format!(
-            "Attempting connections with {:?} peers...",
-            peers.len()
-        ));
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) -> HashMap<Vec<u8>, PeerConnection> {
         let mut connection_attempts = vec![];
         let open_peer_connections = Arc::new(Mutex::new(HashMap::new()));
         for peer in peers {
-            let piece_manager_sender = self.piece_manager_sender.clone();
-            let piece_saver_sender = self.piece_saver_sender.clone();
-            let metainfo = self.metainfo.clone();
-            let client_peer_id = self.client_peer_id.clone();
-            let ui_message_sender = self.ui_message_sender.clone();
+            let DialContext {
+                piece_manager_sender,
+                piece_saver_sender,
+                metainfo,
+                client_peer_id,
+                dial_timeouts,
+                have_pieces,
+                ui_message_sender,
+            } = context.clone();
             let open_peer_connections = open_peer_connections.clone();
             let peer_connection_manager_sender_clone = peer_connection_manager_sender.clone();
             connection_attempts.push(std::thread::spawn(move || {
@@ -113,6 +172,8 @@ impl PeerConnectionManagerWorker {
                     peer_connection_manager_sender_clone,
                     metainfo,
                     &client_peer_id,
+                    &dial_timeouts,
+                    have_pieces,
                     ui_message_sender,
                 ) {
                     if let Ok(mut lock) = open_peer_connections.lock() {
@@ -136,24 +197,189 @@ impl PeerConnectionManagerWorker {
 
         let lock = Arc::try_unwrap(open_peer_connections)
             .expect("no one should have a reference to open_peer_connections");
-        self.peer_connections = lock
-            .into_inner()
-            .expect("should be able to lock open_peer_connections");
+        lock.into_inner()
+            .expect("should be able to lock open_peer_connections")
+    }
+
+    fn start_dial_round(&mut self) {
+        LOGGER.info(format!("Candidate pool: {}", self.candidate_pool.prune()));
+        self.dial_round_running = true;
+        self.candidates_to_dial = self.candidate_pool.len();
+        self.dialed_connections = 0;
+    }
+
+    // Takes the next candidates to dial, or None once the connection cap is reached or every
+    // candidate of the round was dialed once
+    fn next_dial_batch(&mut self) -> Option<Vec<Candidate>> {
+        let open_connections = self.open_peer_connection_count();
+        if self.candidates_to_dial == 0 || open_connections >= MAX_CONNECTIONS {
+            return None;
+        }
+        let free_slots = MAX_CONNECTIONS - open_connections;
+        let batch = self
+            .candidate_pool
+            .next_batch(free_slots.min(DIAL_BATCH_SIZE).min(self.candidates_to_dial));
+        self.candidates_to_dial -= batch.len();
+        Some(batch)
+    }
+
+    fn dial_batch(
+        context: &DialContext,
+        candidates: Vec<Candidate>,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) -> DialedBatch {
+        let peers = candidates
+            .iter()
+            .map(|candidate| candidate.peer.clone())
+            .collect();
+        let connections = Self::connect_to_peers(context, peers, peer_connection_manager_sender);
+        DialedBatch {
+            candidates,
+            connections,
+        }
+    }
+
+    // Failed candidates go back to the pool
+    fn add_dialed_batch(&mut self, dialed_batch: DialedBatch) {
+        let connected: HashSet<(String, u16)> = dialed_batch
+            .connections
+            .values()
+            .map(|peer_connection| peer_address(&peer_connection.peer))
+            .collect();
+        for candidate in dialed_batch.candidates {
+            if !connected.contains(&peer_address(&candidate.peer)) {
+                self.candidate_pool.failed_dial(candidate);
+            }
+        }
+        self.dialed_connections += dialed_batch.connections.len();
+        self.peer_connections.extend(dialed_batch.connections);
+    }
+
+    // Returns the amount of new connections of the round
+    fn finish_dial_round(&mut self) -> usize {
+        self.dial_round_running = false;
         LOGGER.info(format!(
-            "Connected successfully to {:?} peers",
-            self.peer_connections.len()
+            "Connected successfully to {:?} new peers, {:?} candidates left",
+            self.dialed_connections,
+            self.candidate_pool.len()
         ));
+        self.dialed_connections
+    }
+
+    // Dials candidates in batches until the connection cap is reached or every candidate
+    // was dialed once. Returns the amount of new connections
+    fn dial_candidates(
+        &mut self,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) -> usize {
+        self.start_dial_round();
+        let context = self.dial_context();
+        while let Some(candidates) = self.next_dial_batch() {
+            let dialed_batch =
+                Self::dial_batch(&context, candidates, peer_connection_manager_sender);
+            self.add_dialed_batch(dialed_batch);
+        }
+        self.finish_dial_round()
+    }
+
+    // Dials the next batch of the round on another thread, which tells the worker when it is
+    // done. Once the round is over the piece manager gets the new connections
+    fn dial_next_batch_in_background(
+        &mut self,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) {
+        match self.next_dial_batch() {
+            Some(candidates) => {
+                let context = self.dial_context();
+                let sender = peer_connection_manager_sender.clone();
+                self.dialing = Some(std::thread::spawn(move || {
+                    let dialed_batch = Self::dial_batch(&context, candidates, &sender);
+                    sender.finished_dialing();
+                    dialed_batch
+                }));
+            }
+            None => {
+                let new_connections = self.finish_dial_round();
+                self.piece_manager_sender
+                    .finished_stablishing_connections(new_connections);
+            }
+        }
+    }
+
+    // Adds the batch dialed in the background, then handles the messages about its peers
+    fn finished_dialing(
+        &mut self,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) -> bool {
+        if let Some(handle) = self.dialing.take() {
+            match handle.join() {
+                Ok(dialed_batch) => self.add_dialed_batch(dialed_batch),
+                Err(_) => LOGGER.error("Dialing thread panicked".to_string()),
+            }
+        }
+        for message in std::mem::take(&mut self.deferred_messages) {
+            if self.handle_message(message, peer_connection_manager_sender) {
+                return true;
+            }
+        }
+        if self.dialing.is_none() {
+            self.dial_next_batch_in_background(peer_connection_manager_sender);
+        }
+        false
+    }
+
+    // Peers we already have a connection with (open or not) are never dialed again
+    fn ingest_candidates(&mut self, peers: Vec<Peer>) {
+        let known_addresses: HashSet<(String, u16)> = self
+            .peer_connections
+            .values()
+            .map(|peer_connection| peer_address(&peer_connection.peer))
+            .collect();
+        let received = peers.len();
+        let added = self
+            .candidate_pool
+            .ingest(peers, |peer| known_addresses.contains(&peer_address(peer)));
+        LOGGER.info(format!(
+            "Received {:?} peers, {:?} of them added as candidates",
+            received, added
+        ));
+    }
+
+    pub fn start_peer_connections(
+        &mut self,
+        peers: Vec<Peer>,
+        peer_connection_manager_sender: PeerConnectionManagerSender,
+    ) {
+        self.ingest_candidates(peers);
+        let new_connections = self.dial_candidates(&peer_connection_manager_sender);
 
         self.piece_manager_sender
-            .finished_stablishing_connections(self.peer_connections.len());
+            .finished_stablishing_connections(new_connections);
     }
 
-    fn download_piece(&self, peer_id: Vec<u8>, piece_index: u32) {
-        let peer_connection = self.peer_connections.get(&peer_id).unwrap();
-        peer_connection.sender.download_piece(piece_index);
+    // Replaces a failed connection with candidates from the pool, dialed in the background.
+    // The piece manager is told we are looking for peers before the failure, so it does not
+    // give up on the remaining pieces, and gets exactly one finished message for it once the
+    // round is over. A round already running takes the freed slot
+    fn replace_failed_connection(
+        &mut self,
+        peer_id: Vec<u8>,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) {
+        if self.dial_round_running || self.candidate_pool.is_empty() {
+            self.piece_manager_sender.failed_connection(peer_id);
+            return;
+        }
+        self.piece_manager_sender.reasked_tracker();
+        self.piece_manager_sender.failed_connection(peer_id);
+        self.start_dial_round();
+        self.dial_next_batch_in_background(peer_connection_manager_sender);
     }
 
-    fn close_connections(self) {
+    fn close_connections(mut self) {
+        if let Some(Ok(dialed_batch)) = self.dialing.take().map(|handle| handle.join()) {
+            self.add_dialed_batch(dialed_batch);
+        }
         for (_, peer_connection) in self.peer_connections.into_iter() {
             peer_connection.sender.close_connection();
             peer_connection.handle.join().unwrap();
@@ -167,36 +393,37 @@ impl PeerConnectionManagerWorker {
         }
     }
 
-    pub fn listen(
-        mut self,
-        _tracker_service: &mut impl ITrackerService,
-        interval: Option<Duration>,
-        _peer_connection_manager_sender: PeerConnectionManagerSender,
-    ) -> Result<(), RecvError> {
-        loop {
-            let message = self.receiver.recv()?;
-            trace!("Peer connection manager received message: {:?}", message);
+    // The peers of the batch being dialed are not known until the batch is added
+    fn is_dialing_peer(&self, peer_id: &[u8]) -> bool {
+        self.dialing.is_some() && !self.peer_connections.contains_key(peer_id)
+    }
 
-            match message {
-                PeerConnectionManagerMessage::CloseConnections => {
-                    trace!("Closing connections");
-                    self.close_connections();
-                    break;
-                }
-                PeerConnectionManagerMessage::DownloadPiece(peer_id, piece_index) => {
-                    if self.peer_connections[&peer_id].is_open {
+    // Returns true once the connections were closed
+    fn handle_message(
+        &mut self,
+        message: PeerConnectionManagerMessage,
+        peer_connection_manager_sender: &PeerConnectionManagerSender,
+    ) -> bool {
+        match message {
+            PeerConnectionManagerMessage::DownloadPiece(ref peer_id, _)
+            | PeerConnectionManagerMessage::FailedConnection(ref peer_id)
+                if self.is_dialing_peer(peer_id) =>
+            {
+                self.deferred_messages.push(message);
+            }
+            PeerConnectionManagerMessage::DownloadPiece(peer_id, piece_index) => {
+                match self.peer_connections.get_mut(&peer_id) {
+                    Some(peer_connection) if peer_connection.is_open => {
                         LOGGER.debug(format!(
                             "Sending download request {} to peer {:?} with piece requests: {}",
                             piece_index,
-                            self.peer_connections[&peer_id].peer.peer_id,
-                            self.peer_connections[&peer_id].piece_request_count
+                            peer_connection.peer.peer_id,
+                            peer_connection.piece_request_count
                         ));
-                        self.download_piece(peer_id.clone(), piece_index);
-                        self.peer_connections
-                            .get_mut(&peer_id)
-                            .unwrap()
-                            .piece_request_count += 1;
-                    } else {
+                        peer_connection.sender.download_piece(piece_index);
+                        peer_connection.piece_request_count += 1;
+                    }
+                    _ => {
                         LOGGER.error(format!(
                             "Tried to download piece from closed peer connection {:?}, so send it back to retry",
                             peer_id
@@ -204,26 +431,154 @@ impl PeerConnectionManagerWorker {
                         self.piece_manager_sender
                             .failed_download(piece_index, peer_id);
                     }
-                    // print peers that have not yet downloaded a piece
-                    let mut peers = vec![];
-                    for (_, peer_connection) in self.peer_connections.iter() {
-                        if peer_connection.piece_request_count == 0 {
-                            peers.push(peer_connection.peer.clone());
-                        }
-                    }
-
-                    if self.interval_long_enough(interval) {
-                        //let _ = tracker_service.announce(None);
-                        self.last_announce = Instant::now();
-                    }
                 }
+            }
+            PeerConnectionManagerMessage::FailedConnection(peer_id) => {
+                self.set_peer_connection_to_closed(peer_id.clone());
+                self.replace_failed_connection(peer_id, peer_connection_manager_sender);
+            }
+            PeerConnectionManagerMessage::FinishedDialing => {
+                return self.finished_dialing(peer_connection_manager_sender);
+            }
+            PeerConnectionManagerMessage::CloseConnections => return true,
+        }
+        false
+    }
 
-                PeerConnectionManagerMessage::FailedConnection(peer_id) => {
-                    self.set_peer_connection_to_closed(peer_id.clone());
-                    self.piece_manager_sender.failed_connection(peer_id);
-                }
+    pub fn listen(
+        mut self,
+        _tracker_service: &mut impl ITrackerService,
+        interval: Option<Duration>,
+        peer_connection_manager_sender: PeerConnectionManagerSender,
+    ) -> Result<(), RecvError> {
+        loop {
+            let message = self.receiver.recv()?;
+            trace!("Peer connection manager received message: {:?}", message);
+
+            if self.handle_message(message, &peer_connection_manager_sender) {
+                trace!("Closing connections");
+                self.close_connections();
+                break;
+            }
+            if self.interval_long_enough(interval) {
+                //let _ = tracker_service.announce(None);
+                self.last_announce = Instant::now();
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::metainfo::Info;
+    use crate::peer_connection_manager::types::new_peer_connection_manager;
+    use crate::piece_manager::PieceManagerMessage;
+    use std::sync::mpsc;
+
+    fn refused_provider(
+        _ip: String,
+        _port: u16,
+        _connect_timeout: Duration,
+    ) -> Result<Box<dyn IClientPeerMessageService + Send>, PeerConnectionError> {
+        Err(PeerConnectionError::InitialConnectionError(
+            "Connection refused".to_string(),
+        ))
+    }
+
+    fn unreachable_peer(port: u16) -> Peer {
+        Peer {
+            ip: "10.0.0.1".to_string(),
+            port,
+            peer_id: vec![port as u8],
+            peer_message_service_provider: refused_provider,
+        }
+    }
+
+    fn worker_with_candidates(
+        peers: Vec<Peer>,
+    ) -> (
+        PeerConnectionManagerSender,
+        PeerConnectionManagerWorker,
+        mpsc::Receiver<PieceManagerMessage>,
+    ) {
+        let (piece_manager_tx, piece_manager_rx) = mpsc::channel();
+        let (piece_saver_tx, _) = mpsc::channel();
+        let metainfo = Metainfo {
+            announce: "".to_string(),
+            announce_list: vec![],
+            info: Info {
+                piece_length: 8,
+                pieces: vec![vec![0; 20]],
+                length: 8,
+                name: "".to_string(),
+                files: None,
+            },
+            info_hash: vec![0; 20],
+        };
+        let config = Config {
+            listen_port: 0,
+            log_path: "".to_string(),
+            download_path: "".to_string(),
+            persist_pieces: false,
+            candidate_ttl: Duration::from_secs(60),
+            candidate_max_failed_dials: 3,
+            dial_timeouts: DialTimeouts {
+                lan: Duration::from_millis(100),
+                wan: Duration::from_millis(100),
+            },
+        };
+        let (sender, mut worker) = new_peer_connection_manager(
+            PieceManagerSender {
+                sender: piece_manager_tx,
+            },
+            PieceSaverSender {
+                sender: piece_saver_tx,
+            },
+            &metainfo,
+            &[0; 20],
+            &config,
+            Arc::new(RwLock::new(Bitfield::with_len(1))),
+            UIMessageSender::no_ui(),
+        );
+        worker.ingest_candidates(peers);
+        (sender, worker, piece_manager_rx)
+    }
+
+    #[test]
+    fn a_failure_during_a_dial_round_does_not_start_another_one() {
+        let (sender, mut worker, piece_manager_rx) =
+            worker_with_candidates(vec![unreachable_peer(1), unreachable_peer(2)]);
+
+        worker.handle_message(
+            PeerConnectionManagerMessage::FailedConnection(vec![100]),
+            &sender,
+        );
+        // fails while the batch is dialed, so it is replayed once the batch is added
+        worker.handle_message(
+            PeerConnectionManagerMessage::FailedConnection(vec![101]),
+            &sender,
+        );
+        let finished_dialing = worker.receiver.recv().unwrap();
+        worker.handle_message(finished_dialing, &sender);
+
+        let messages: Vec<PieceManagerMessage> = piece_manager_rx.try_iter().collect();
+        let reasks = messages
+            .iter()
+            .filter(|message| matches!(message, PieceManagerMessage::ReaskedTracker()))
+            .count();
+        let finished: Vec<usize> = messages
+            .iter()
+            .filter_map(|message| match message {
+                PieceManagerMessage::FinishedEstablishingConnections(count) => Some(*count),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reasks, 1);
+        assert_eq!(finished, vec![0]);
+        assert!(worker.dialing.is_none());
+        assert!(!worker.dial_round_running);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod sender;
+pub mod types;
+pub mod worker;
+
+pub use sender::PieceHasherSender;
+pub use types::new_piece_hasher;
+pub use worker::PieceHasherWorker;
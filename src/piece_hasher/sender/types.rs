@@ -0,0 +1,19 @@
+use crate::piece_hasher::types::PieceHasherMessage;
+use std::sync::mpsc::Sender;
+
+#[derive(Clone)]
+pub struct PieceHasherSender {
+    pub sender: Sender<PieceHasherMessage>,
+}
+
+impl PieceHasherSender {
+    pub fn stop_hashing(&self) {
+        let _ = self.sender.send(PieceHasherMessage::StopHashing);
+    }
+
+    pub fn hash_piece(&self, piece_index: u32, piece_bytes: Vec<u8>) {
+        let _ = self
+            .sender
+            .send(PieceHasherMessage::HashPiece(piece_index, piece_bytes));
+    }
+}
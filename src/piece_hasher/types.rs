@@ -0,0 +1,28 @@
+use super::sender::types::PieceHasherSender;
+use super::worker::types::PieceHasherWorker;
+use crate::piece_saver::PieceSaverSender;
+use std::sync::mpsc;
+
+#[derive(Debug)]
+pub enum PieceHasherMessage {
+    HashPiece(u32, Vec<u8>),
+    StopHashing,
+}
+
+/// Creates a hasher that validates pieces against `sha1_pieces` and reports every result
+/// back to the piece saver, in the same order the pieces were submitted.
+pub fn new_piece_hasher(
+    sha1_pieces: Vec<Vec<u8>>,
+    piece_saver_sender: PieceSaverSender,
+) -> (PieceHasherSender, PieceHasherWorker) {
+    let (tx, rx) = mpsc::channel();
+
+    (
+        PieceHasherSender { sender: tx },
+        PieceHasherWorker {
+            receiver: rx,
+            sha1_pieces,
+            piece_saver_sender,
+        },
+    )
+}
@@ -0,0 +1,104 @@
+use crate::logger::CustomLogger;
+use crate::peer::sha1_of;
+use crate::piece_hasher::types::PieceHasherMessage;
+use crate::piece_saver::PieceSaverSender;
+use log::*;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvError;
+
+const LOGGER: CustomLogger = CustomLogger::init("Piece Hasher");
+
+/// Hashes pieces outside of the piece saver loop. Jobs are processed one at a time in the
+/// order they arrive, so completions reach the piece saver in submission order.
+pub struct PieceHasherWorker {
+    pub receiver: Receiver<PieceHasherMessage>,
+    pub sha1_pieces: Vec<Vec<u8>>,
+    pub piece_saver_sender: PieceSaverSender,
+}
+
+impl PieceHasherWorker {
+    fn valid_piece(&self, piece_bytes: &[u8], piece_index: u32) -> bool {
+        match self.sha1_pieces.get(piece_index as usize) {
+            Some(real_piece_sha1) => sha1_of(piece_bytes) == *real_piece_sha1,
+            None => false,
+        }
+    }
+
+    pub fn listen(&self) -> Result<(), RecvError> {
+        loop {
+            let message = self.receiver.recv()?;
+
+            match message {
+                PieceHasherMessage::StopHashing => {
+                    LOGGER.info_str("Stopping Piece Hasher Worker");
+                    break;
+                }
+                PieceHasherMessage::HashPiece(piece_index, piece_bytes) => {
+                    trace!("Piece hasher received piece: {:?}", piece_index);
+                    let is_valid = self.valid_piece(&piece_bytes, piece_index);
+                    self.piece_saver_sender
+                        .piece_hashed(piece_index, piece_bytes, is_valid);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece_hasher::new_piece_hasher;
+    use crate::piece_saver::types::PieceSaverMessage;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_completions_arrive_in_submission_order() {
+        let pieces: Vec<Vec<u8>> = (0..20u8)
+            .map(|i| vec![i; 1024 * (i as usize + 1)])
+            .collect();
+        let sha1_pieces = pieces.iter().map(|piece| sha1_of(piece)).collect();
+        let (tx, rx) = mpsc::channel();
+        let (sender, worker) = new_piece_hasher(sha1_pieces, PieceSaverSender { sender: tx });
+        let handle = std::thread::spawn(move || worker.listen());
+
+        let order: Vec<u32> = vec![5, 0, 19, 3, 7, 1, 12];
+        for piece_index in &order {
+            sender.hash_piece(*piece_index, pieces[*piece_index as usize].clone());
+        }
+        sender.stop_hashing();
+        let _ = handle.join();
+
+        let completed: Vec<u32> = rx
+            .iter()
+            .map(|message| match message {
+                PieceSaverMessage::PieceHashed(piece_index, _, is_valid) => {
+                    assert!(is_valid);
+                    piece_index
+                }
+                _ => panic!("unexpected message"),
+            })
+            .collect();
+        assert_eq!(completed, order);
+    }
+
+    #[test]
+    fn test_invalid_piece_is_reported() {
+        let (tx, rx) = mpsc::channel();
+        let (sender, worker) =
+            new_piece_hasher(vec![sha1_of(b"piece")], PieceSaverSender { sender: tx });
+        let handle = std::thread::spawn(move || worker.listen());
+
+        sender.hash_piece(0, b"corrupted".to_vec());
+        sender.hash_piece(1, b"piece".to_vec());
+        sender.stop_hashing();
+        let _ = handle.join();
+
+        for _ in 0..2 {
+            match rx.recv().unwrap() {
+                PieceSaverMessage::PieceHashed(_, _, is_valid) => assert!(!is_valid),
+                _ => panic!("unexpected message"),
+            }
+        }
+    }
+}
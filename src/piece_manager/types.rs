@@ -1,11 +1,12 @@
 use super::sender::types::PieceManagerSender;
 use super::worker::types::PieceManagerWorker;
+use crate::event_bus::UIMessageSender;
 use crate::peer::Bitfield;
-use crate::ui::UIMessageSender;
 
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
 
 type PeerId = Vec<u8>;
 type PieceId = u32;
@@ -36,6 +37,11 @@ pub fn new_piece_manager(
         }
     }
 
+    let mut have_pieces = Bitfield::with_len(number_of_pieces as usize);
+    for piece in &initial_pieces {
+        have_pieces.set_piece(*piece as usize);
+    }
+
     // Initialize remaining_pieces HashSet with all pieces
     let mut remaining_pieces: HashSet<PieceId> = HashSet::new();
     for i in 0..number_of_pieces {
@@ -49,6 +55,7 @@ pub fn new_piece_manager(
         PieceManagerWorker {
             reciever: rx,
             allowed_peers_to_download_piece: peers_per_piece,
+            have_pieces: Arc::new(RwLock::new(have_pieces)),
             ui_message_sender,
             is_downloading: false,
             piece_asked_to: HashMap::new(),
@@ -1,25 +1,30 @@
+use crate::event_bus::UIMessageSender;
 use crate::logger::CustomLogger;
 use crate::peer::Bitfield;
 use crate::peer_connection_manager::PeerConnectionManagerSender;
 use crate::piece_manager::types::PieceManagerMessage;
-use crate::ui::UIMessageSender;
 use log::*;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::RecvError;
+use std::sync::{Arc, RwLock};
 
 const LOGGER: CustomLogger = CustomLogger::init("Piece Manager");
 type PeerId = Vec<u8>;
 pub struct PieceManagerWorker {
     pub reciever: Receiver<PieceManagerMessage>,
     pub allowed_peers_to_download_piece: HashMap<u32, Vec<PeerId>>,
+    /// pieces already downloaded, shared with the peer connections to decide interest
+    pub have_pieces: Arc<RwLock<Bitfield>>,
     pub ready_to_download_pieces: HashSet<u32>,
     pub ui_message_sender: UIMessageSender,
     pub is_downloading: bool,
     pub piece_asked_to: HashMap<u32, PeerId>,
     pub pieces_without_peer: HashSet<u32>,
     pub peer_pieces_to_download_count: HashMap<PeerId, u32>,
+    /// bitfields received and connections opened in the latest dialing round. Every new
+    /// connection sends one bitfield, and both are reset once the round is handled
     pub recieved_bitfields: usize,
     pub established_connections: usize,
     pub is_asking_tracker: bool,
@@ -29,14 +34,15 @@ impl PieceManagerWorker {
     fn update_after_succesfull_download(&mut self, piece_index: u32, peerd_id: PeerId) {
         self.ready_to_download_pieces.remove(&piece_index);
         self.allowed_peers_to_download_piece.remove(&piece_index);
+        if let Ok(mut have_pieces) = self.have_pieces.write() {
+            have_pieces.set_piece(piece_index as usize);
+        }
         self.piece_asked_to.remove(&piece_index);
 
-        // this unwrap would never happen peer would only be removed once the connection fails
-        let count = self
-            .peer_pieces_to_download_count
-            .get_mut(&peerd_id)
-            .unwrap();
-        *count -= 1;
+        // the peer may be gone already if the piece was quarantined before being saved
+        if let Some(count) = self.peer_pieces_to_download_count.get_mut(&peerd_id) {
+            *count -= 1;
+        }
     }
 
     fn piece_succesfully_downloaded(
@@ -81,16 +87,24 @@ impl PieceManagerWorker {
     }
 
     fn update_peers_per_piece(&mut self, bitfield: &Bitfield, peer_id: Vec<u8>) {
-        self.allowed_peers_to_download_piece
-            .iter_mut()
-            .for_each(|(piece_number, peer_ids)| {
-                if bitfield.has_piece(*piece_number as usize) {
-                    peer_ids.push(peer_id.clone());
-                    self.peer_pieces_to_download_count
-                        .entry(peer_id.clone())
-                        .or_insert(0);
-                }
-            });
+        // only the pieces the peer has that we still need
+        let needed_pieces = match self.have_pieces.read() {
+            Ok(have_pieces) => bitfield.and_not(&have_pieces),
+            Err(_) => bitfield.clone(),
+        };
+        for piece_number in needed_pieces.iter_ones() {
+            if let Some(peer_ids) = self
+                .allowed_peers_to_download_piece
+                .get_mut(&(piece_number as u32))
+            {
+                peer_ids.push(peer_id.clone());
+            }
+        }
+        if needed_pieces.count_ones() > 0 {
+            self.peer_pieces_to_download_count
+                .entry(peer_id)
+                .or_insert(0);
+        }
         self.recieved_bitfields += 1;
     }
 
@@ -268,10 +282,15 @@ impl PieceManagerWorker {
                 PieceManagerMessage::PeerPieces(peer_id, bitfield) => {
                     trace!("Piece manager received bitfield from peer: {:?}", peer_id);
                     self.update_peers_per_piece(&bitfield, peer_id.clone());
-                    if self.established_connections != 0 {
+                    if self.is_asking_tracker {
+                        self.start_downloading_or_ask_pieces_with_no_peers_if_ready(
+                            &peer_connection_manager_sender,
+                        );
+                    } else if self.established_connections != 0 {
                         self.ask_for_pieces(&peer_connection_manager_sender);
                     }
                 }
+                // Sent once for every round, with the connections opened in that round only
                 PieceManagerMessage::FinishedEstablishingConnections(connections_established) => {
                     info!("Piece manager received finished stablishing connections");
                     self.established_connections = connections_established;
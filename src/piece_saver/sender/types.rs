@@ -23,4 +23,12 @@ impl PieceSaverSender {
             piece_bytes,
         ));
     }
+
+    pub fn piece_hashed(&self, piece_index: u32, piece_bytes: Vec<u8>, is_valid: bool) {
+        let _ = self.sender.send(PieceSaverMessage::PieceHashed(
+            piece_index,
+            piece_bytes,
+            is_valid,
+        ));
+    }
 }
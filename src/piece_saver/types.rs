@@ -1,12 +1,17 @@
 use super::sender::types::PieceSaverSender;
 use super::worker::types::PieceSaverWorker;
+use crate::event_bus::UIMessageSender;
+use crate::piece_hasher::new_piece_hasher;
 use crate::piece_manager::sender::PieceManagerSender;
-use crate::ui::UIMessageSender;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::sync::mpsc;
+use std::time::Instant;
 
 #[derive(Debug)]
 pub enum PieceSaverMessage {
     ValidateAndSavePiece(u32, Vec<u8>, Vec<u8>),
+    PieceHashed(u32, Vec<u8>, bool),
     StopSaving,
 }
 
@@ -17,15 +22,23 @@ pub fn new_piece_saver(
     ui_message_sender: UIMessageSender,
 ) -> (PieceSaverSender, PieceSaverWorker) {
     let (tx, rx) = mpsc::channel();
+    let piece_saver_sender = PieceSaverSender { sender: tx };
+    let (piece_hasher_sender, piece_hasher_worker) =
+        new_piece_hasher(sha1_pieces.clone(), piece_saver_sender.clone());
 
     (
-        PieceSaverSender { sender: tx },
+        piece_saver_sender,
         PieceSaverWorker {
             receiver: rx,
             piece_manager_sender,
+            piece_hasher_sender,
+            piece_hasher_worker: Some(piece_hasher_worker),
+            pending_hashes: VecDeque::new(),
             sha1_pieces,
             download_path,
             ui_message_sender,
+            quarantined_pieces: BTreeMap::new(),
+            next_quarantine_retry: Instant::now(),
         },
     )
 }
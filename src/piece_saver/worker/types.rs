@@ -1,51 +1,161 @@
-use crate::download_manager::save_piece_in_disk;
-use crate::download_manager::Piece;
+use crate::download_manager::{
+    restore_quarantined_piece, save_piece_in_disk, DownloadManagerError, Piece,
+};
+use crate::event_bus::UIMessageSender;
 use crate::logger::{CustomLogger, Logger};
+use crate::piece_hasher::{PieceHasherSender, PieceHasherWorker};
 use crate::piece_manager::sender::PieceManagerSender;
 use crate::piece_saver::types::PieceSaverMessage;
-use crate::ui::UIMessageSender;
 use log::*;
-use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::sync::mpsc::Receiver;
-use std::sync::mpsc::RecvError;
+use std::sync::mpsc::{RecvError, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
 const LOGGER: CustomLogger = CustomLogger::init("Piece Saver");
 
+/// Times a verified piece is written to the pieces dir before spooling it to quarantine
+pub const MAX_SAVE_ATTEMPTS: u32 = 3;
+/// Wait after the first failed write, doubled after each failed attempt
+pub const SAVE_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// Time between attempts to move quarantined pieces to the pieces dir
+pub const QUARANTINE_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
 pub struct PieceSaverWorker {
     pub receiver: Receiver<PieceSaverMessage>,
     pub piece_manager_sender: PieceManagerSender,
+    pub piece_hasher_sender: PieceHasherSender,
+    pub piece_hasher_worker: Option<PieceHasherWorker>,
+    // Pieces sent to the hasher, in submission order, with the peer that sent them
+    pub pending_hashes: VecDeque<(u32, Vec<u8>)>,
+    // Expected hash of every piece, to check quarantined pieces before restoring them
     pub sha1_pieces: Vec<Vec<u8>>,
     pub download_path: String,
     pub ui_message_sender: UIMessageSender,
+    // Verified pieces that could only be written to the quarantine dir, with the peer that sent
+    // them. They are reported to the piece manager once they reach the pieces dir
+    pub quarantined_pieces: BTreeMap<u32, Vec<u8>>,
+    pub next_quarantine_retry: Instant,
+}
+
+// Where a verified piece ended up
+#[derive(Debug, PartialEq)]
+enum SavedPiece {
+    Saved,
+    Quarantined,
+    Lost,
 }
 
 impl PieceSaverWorker {
-    fn sha1_of(&self, vec: &[u8]) -> Vec<u8> {
-        let mut hasher = Sha1::new();
-        hasher.update(vec);
-        hasher.finalize().to_vec()
+    fn pieces_dir(&self) -> String {
+        format!("{}/pieces", self.download_path)
     }
 
-    fn valid_piece(&self, piece_bytes: &[u8], piece_index: u32) -> bool {
-        let real_piece_sha1 = self.sha1_pieces[piece_index as usize].to_vec();
-        let recieved_piece_sha1 = self.sha1_of(piece_bytes);
-        recieved_piece_sha1 == real_piece_sha1
+    fn quarantine_dir(&self) -> String {
+        format!("{}/quarantine", self.download_path)
     }
 
-    fn make_validation_and_save_piece(&self, piece_index: u32, piece_bytes: Vec<u8>) -> bool {
-        if !self.valid_piece(&piece_bytes, piece_index) {
-            return false;
-        }
-
+    // Writes a verified piece to the pieces dir, backing off between attempts, or spools it to
+    // the quarantine dir when that keeps failing
+    fn save_piece(
+        &mut self,
+        piece_index: u32,
+        piece_bytes: Vec<u8>,
+        peer_id: Vec<u8>,
+    ) -> SavedPiece {
         let piece = Piece {
             piece_number: piece_index,
             data: piece_bytes,
         };
 
-        let download_path = format!("{}/pieces", String::from(&self.download_path));
-        match save_piece_in_disk(&piece, &download_path) {
-            Ok(()) => true,
-            Err(_) => false,
+        let pieces_dir = self.pieces_dir();
+        let mut retry_delay = SAVE_RETRY_DELAY;
+        for attempt in 1..=MAX_SAVE_ATTEMPTS {
+            match save_piece_in_disk(&piece, &pieces_dir) {
+                Ok(()) => return SavedPiece::Saved,
+                Err(err) => LOGGER.error(format!(
+                    "Attempt {} to save piece {} failed: {}",
+                    attempt, piece_index, err
+                )),
+            }
+            if attempt < MAX_SAVE_ATTEMPTS {
+                std::thread::sleep(retry_delay);
+                retry_delay *= 2;
+            }
+        }
+
+        match save_piece_in_disk(&piece, &self.quarantine_dir()) {
+            Ok(()) => {
+                LOGGER.error(format!(
+                    "Piece {} was quarantined, saving it will be retried",
+                    piece_index
+                ));
+                if self.quarantined_pieces.is_empty() {
+                    self.next_quarantine_retry = Instant::now() + QUARANTINE_RETRY_INTERVAL;
+                }
+                self.quarantined_pieces.insert(piece_index, peer_id);
+                SavedPiece::Quarantined
+            }
+            Err(err) => {
+                LOGGER.error(format!(
+                    "Could not quarantine piece {}, it will be downloaded again: {}",
+                    piece_index, err
+                ));
+                SavedPiece::Lost
+            }
+        }
+    }
+
+    // Quarantined pieces that reach the pieces dir are reported as downloaded, and corrupt
+    // ones as failed so they are downloaded again
+    fn retry_quarantined_pieces(&mut self, logger: &Logger) {
+        let (pieces_dir, quarantine_dir) = (self.pieces_dir(), self.quarantine_dir());
+        let quarantined_pieces = std::mem::take(&mut self.quarantined_pieces);
+        for (piece_index, peer_id) in quarantined_pieces {
+            let expected_sha1 = self
+                .sha1_pieces
+                .get(piece_index as usize)
+                .map(|sha1| sha1.as_slice())
+                .unwrap_or_default();
+            match restore_quarantined_piece(
+                piece_index,
+                expected_sha1,
+                &quarantine_dir,
+                &pieces_dir,
+            ) {
+                Ok(()) => {
+                    LOGGER.info(format!("Quarantined piece {} was saved", piece_index));
+                    self.downloaded_piece_successfully(piece_index, peer_id, logger);
+                }
+                Err(DownloadManagerError::CorruptPieceError(_)) => {
+                    LOGGER.error(format!(
+                        "Quarantined piece {} is corrupt, it will be downloaded again",
+                        piece_index
+                    ));
+                    self.piece_manager_sender
+                        .failed_download(piece_index, peer_id);
+                }
+                Err(_) => {
+                    self.quarantined_pieces.insert(piece_index, peer_id);
+                }
+            }
+        }
+        self.next_quarantine_retry = Instant::now() + QUARANTINE_RETRY_INTERVAL;
+    }
+
+    // Waits for the next message, or until quarantined pieces have to be retried
+    fn next_message(&self) -> Result<Option<PieceSaverMessage>, RecvError> {
+        if self.quarantined_pieces.is_empty() {
+            return self.receiver.recv().map(Some);
+        }
+        let timeout = self
+            .next_quarantine_retry
+            .saturating_duration_since(Instant::now());
+        match self.receiver.recv_timeout(timeout) {
+            Ok(message) => Ok(Some(message)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(RecvError),
         }
     }
 
@@ -57,34 +167,205 @@ impl PieceSaverWorker {
         let _ = logger.log_piece(piece_index);
     }
 
-    pub fn listen(&self) -> Result<(), RecvError> {
+    fn piece_hashed(
+        &mut self,
+        piece_index: u32,
+        piece_bytes: Vec<u8>,
+        is_valid: bool,
+        logger: &Logger,
+    ) {
+        // the hasher answers in submission order, so the piece is the oldest one sent to it
+        let is_next = matches!(
+            self.pending_hashes.front(),
+            Some((pending_index, _)) if *pending_index == piece_index
+        );
+        if !is_next {
+            LOGGER.error(format!(
+                "Piece {} was hashed but it was not the next piece sent to the hasher, ignoring it",
+                piece_index
+            ));
+            return;
+        }
+        let peer_id = match self.pending_hashes.pop_front() {
+            Some((_, peer_id)) => peer_id,
+            None => return,
+        };
+
+        if !is_valid {
+            self.piece_manager_sender
+                .failed_download(piece_index, peer_id);
+            return;
+        }
+        match self.save_piece(piece_index, piece_bytes, peer_id.clone()) {
+            SavedPiece::Saved => self.downloaded_piece_successfully(piece_index, peer_id, logger),
+            SavedPiece::Quarantined => {}
+            SavedPiece::Lost => self
+                .piece_manager_sender
+                .failed_download(piece_index, peer_id),
+        }
+    }
+
+    fn validate_piece(&mut self, piece_index: u32, peer_id: Vec<u8>, piece_bytes: Vec<u8>) {
+        trace!("Piece saver received piece: {:?}", piece_index);
+        self.pending_hashes.push_back((piece_index, peer_id));
+        self.piece_hasher_sender
+            .hash_piece(piece_index, piece_bytes);
+    }
+
+    // Waits for the hasher to answer every piece sent to it, so no piece is left assigned
+    // to a peer in the piece manager when stopping
+    fn wait_for_pending_hashes(&mut self, logger: &Logger) -> Result<(), RecvError> {
+        while !self.pending_hashes.is_empty() {
+            match self.receiver.recv()? {
+                PieceSaverMessage::ValidateAndSavePiece(piece_index, peer_id, piece_bytes) => {
+                    self.validate_piece(piece_index, peer_id, piece_bytes);
+                }
+                PieceSaverMessage::PieceHashed(piece_index, piece_bytes, is_valid) => {
+                    self.piece_hashed(piece_index, piece_bytes, is_valid, logger);
+                }
+                PieceSaverMessage::StopSaving => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn listen(&mut self) -> Result<(), RecvError> {
         let (logger, handle) = Logger::new("./logs").unwrap();
+        let hasher_handle = self
+            .piece_hasher_worker
+            .take()
+            .map(|piece_hasher_worker| std::thread::spawn(move || piece_hasher_worker.listen()));
 
         loop {
-            let message = self.receiver.recv()?;
+            let message = match self.next_message()? {
+                Some(message) => message,
+                None => {
+                    self.retry_quarantined_pieces(&logger);
+                    continue;
+                }
+            };
 
             match message {
                 PieceSaverMessage::StopSaving => {
                     LOGGER.info_str("Stopping Piece Saver Worker");
+                    self.wait_for_pending_hashes(&logger)?;
                     break;
                 }
                 PieceSaverMessage::ValidateAndSavePiece(piece_index, peer_id, piece_bytes) => {
-                    trace!("Piece saver received piece: {:?}", piece_index);
-                    let successfuly_downloaded: bool =
-                        self.make_validation_and_save_piece(piece_index, piece_bytes);
-
-                    if successfuly_downloaded {
-                        self.downloaded_piece_successfully(piece_index, peer_id, &logger);
-                    } else {
-                        self.piece_manager_sender
-                            .failed_download(piece_index, peer_id);
-                    }
+                    self.validate_piece(piece_index, peer_id, piece_bytes);
+                }
+                PieceSaverMessage::PieceHashed(piece_index, piece_bytes, is_valid) => {
+                    self.piece_hashed(piece_index, piece_bytes, is_valid, &logger);
                 }
             }
         }
 
+        if !self.quarantined_pieces.is_empty() {
+            self.retry_quarantined_pieces(&logger);
+        }
+        if !self.quarantined_pieces.is_empty() {
+            LOGGER.error(format!(
+                "Pieces {:?} are still quarantined, they will be restored on the next run",
+                self.quarantined_pieces.keys()
+            ));
+        }
+
+        self.piece_hasher_sender.stop_hashing();
+        if let Some(hasher_handle) = hasher_handle {
+            let _ = hasher_handle.join();
+        }
         logger.stop();
         let _ = handle.join();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SavedPiece;
+    use crate::event_bus::UIMessageSender;
+    use crate::logger::Logger;
+    use crate::peer::sha1_of;
+    use crate::piece_manager::sender::PieceManagerSender;
+    use crate::piece_manager::types::PieceManagerMessage;
+    use crate::piece_saver::types::new_piece_saver;
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_quarantined_piece_is_saved_on_retry() {
+        let download_path = "./src/piece_saver/test_quarantine";
+        let _ = std::fs::remove_dir_all(download_path);
+        std::fs::create_dir_all(download_path).unwrap();
+        // a file where the pieces dir should be makes every save fail
+        std::fs::write(format!("{}/pieces", download_path), b"").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let mut sha1_pieces = vec![vec![]; 8];
+        sha1_pieces[7] = sha1_of(&[7; 16]);
+        let (_, mut worker) = new_piece_saver(
+            PieceManagerSender { sender: tx },
+            sha1_pieces,
+            download_path.to_string(),
+            UIMessageSender::no_ui(),
+        );
+
+        let (logger, logger_handle) = Logger::new(download_path).unwrap();
+
+        assert_eq!(
+            worker.save_piece(7, vec![7; 16], vec![1]),
+            SavedPiece::Quarantined
+        );
+        assert!(worker.quarantined_pieces.contains_key(&7));
+        assert!(Path::new(&format!("{}/quarantine/7", download_path)).exists());
+        // the piece is not reported until it reaches the pieces dir
+        assert!(rx.try_recv().is_err());
+
+        std::fs::remove_file(format!("{}/pieces", download_path)).unwrap();
+        worker.retry_quarantined_pieces(&logger);
+
+        assert!(worker.quarantined_pieces.is_empty());
+        assert_eq!(
+            std::fs::read(format!("{}/pieces/7", download_path)).unwrap(),
+            vec![7; 16]
+        );
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(PieceManagerMessage::SuccessfulDownload(7, _))
+        ));
+        logger.stop();
+        logger_handle.join().unwrap();
+        std::fs::remove_dir_all(download_path).unwrap();
+    }
+
+    #[test]
+    fn test_pending_hashes_are_reported_before_stopping() {
+        let download_path = "./src/piece_saver/test_pending_hashes";
+        let (tx, rx) = mpsc::channel();
+        let (piece_saver_sender, mut worker) = new_piece_saver(
+            PieceManagerSender { sender: tx },
+            vec![],
+            download_path.to_string(),
+            UIMessageSender::no_ui(),
+        );
+        let (logger, logger_handle) = Logger::new(download_path).unwrap();
+        worker.pending_hashes.push_back((1, vec![1]));
+        worker.pending_hashes.push_back((2, vec![2]));
+        piece_saver_sender.piece_hashed(1, vec![], false);
+        piece_saver_sender.piece_hashed(2, vec![], false);
+
+        worker.wait_for_pending_hashes(&logger).unwrap();
+
+        assert!(worker.pending_hashes.is_empty());
+        let reported: Vec<PieceManagerMessage> = rx.try_iter().collect();
+        assert!(matches!(
+            reported.as_slice(),
+            [
+                PieceManagerMessage::FailedDownload(1, _),
+                PieceManagerMessage::FailedDownload(2, _)
+            ]
+        ));
+        logger.stop();
+        logger_handle.join().unwrap();
+        std::fs::remove_dir_all(download_path).unwrap();
+    }
+}
@@ -113,22 +113,20 @@ impl Server {
             match stream {
                 Ok(stream) => {
                     info!("Server: Incoming connection");
-                    println!(
-                        "handle incomming connection return data:{:?}",
-                        Server::handle_incoming_connection(
-                            stream,
-                            metainfo.clone(),
-                            client_peer_id.clone(),
-                            logger.clone(),
-                            &pool,
-                            pieces_dir,
-                        )
+                    let result = Server::handle_incoming_connection(
+                        stream,
+                        metainfo.clone(),
+                        client_peer_id.clone(),
+                        logger.clone(),
+                        &pool,
+                        pieces_dir,
                     );
+                    debug!("handle incomming connection return data:{:?}", result);
                 }
                 Err(ref err) if err.kind() == std::io::ErrorKind::WouldBlock => {
                     // This doesen't mean an error ocurred, there just wasn't a connection at the moment
                     if last_announce.elapsed().as_secs() > TRACKER_INTERVAL_IN_SECONDS {
-                        println!("announcing");
+                        debug!("Server: announcing to tracker");
                         let _ = tracker_service.announce(None);
                         last_announce = std::time::Instant::now();
                     }
@@ -179,6 +179,7 @@ mod tests {
         pieces.push(sha1_of(&file[8..16].to_vec()));
         Metainfo {
             announce: "".to_string(),
+            announce_list: vec![],
             info: Info {
                 piece_length: 8,
                 pieces: pieces,
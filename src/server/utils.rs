@@ -1,5 +1,6 @@
 use super::RequestMessage;
 use super::ServerError;
+use log::*;
 use std::io::Read;
 use std::path::Path;
 
@@ -75,6 +76,6 @@ pub fn get_pieces_vector(piece_count: usize, download_path: &str) -> Vec<bool> {
     for i in 0..piece_count {
         piece_vector.push(client_has_piece(i, download_path));
     }
-    println!("pieces vector: {:?}", piece_vector);
+    debug!("pieces vector: {:?}", piece_vector);
     piece_vector
 }
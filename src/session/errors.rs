@@ -0,0 +1,50 @@
+use crate::bencode::BencodeDecoderError;
+use crate::config::ConfigError;
+use crate::metainfo::MetainfoParserError;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+/// Errors that can occur when exporting or importing a torrent session
+pub enum SessionError {
+    IoError(io::Error),
+    ConfigError(ConfigError),
+    MetainfoError(MetainfoParserError),
+    /// the session file is not valid bencode or misses some key
+    InvalidSession(String),
+}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> Self {
+        SessionError::IoError(error)
+    }
+}
+
+impl From<ConfigError> for SessionError {
+    fn from(error: ConfigError) -> Self {
+        SessionError::ConfigError(error)
+    }
+}
+
+impl From<MetainfoParserError> for SessionError {
+    fn from(error: MetainfoParserError) -> Self {
+        SessionError::MetainfoError(error)
+    }
+}
+
+impl From<BencodeDecoderError> for SessionError {
+    fn from(error: BencodeDecoderError) -> Self {
+        SessionError::InvalidSession(error.0)
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::IoError(error) => write!(f, "IoError: {}", error),
+            SessionError::ConfigError(error) => write!(f, "Config Error - {}", error),
+            SessionError::MetainfoError(error) => write!(f, "Metainfo Error - {}", error),
+            SessionError::InvalidSession(reason) => write!(f, "Invalid session: {}", reason),
+        }
+    }
+}
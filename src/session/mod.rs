@@ -0,0 +1,5 @@
+mod errors;
+mod types;
+
+pub use errors::SessionError;
+pub use types::{ImportedSession, TorrentSession};
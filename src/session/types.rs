@@ -0,0 +1,314 @@
+use super::errors::SessionError;
+use crate::bencode::{decode, encode, BencodeDecodedValue};
+use crate::config::Config;
+use crate::download_manager::{get_existing_pieces, piece_matches_hash};
+use crate::logger::CustomLogger;
+use crate::metainfo::{parse, Metainfo};
+use crate::peer::{bitmap_from_pieces_vector, Bitfield};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOGGER: CustomLogger = CustomLogger::init("Session");
+
+const SESSION_VERSION: i64 = 1;
+const VERSION: &[u8] = b"version";
+const TORRENT: &[u8] = b"torrent";
+const VERIFIED_PIECES: &[u8] = b"verified pieces";
+const DOWNLOADED: &[u8] = b"downloaded";
+const EXPORTED_AT: &[u8] = b"exported at";
+const SETTINGS: &[u8] = b"settings";
+
+/// Portable state of a torrent: the torrent file, the pieces already verified and saved, its
+/// download stats and the settings it runs with. Paths are left out, so it can be imported on
+/// another machine that has a copy of the data directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TorrentSession {
+    pub torrent: Vec<u8>,
+    pub verified_pieces: Vec<u32>,
+    /// bytes of the torrent downloaded and verified
+    pub downloaded: u64,
+    /// seconds since the unix epoch when the session was exported
+    pub exported_at: u64,
+    pub settings: BTreeMap<String, String>,
+}
+
+/// Files written by an import, the pieces found in the data directory and the stats of the
+/// session
+#[derive(Debug)]
+pub struct ImportedSession {
+    pub torrent_path: String,
+    pub config_path: String,
+    /// pieces of the session that are in the data directory and match their hash
+    pub verified_pieces: Vec<u32>,
+    /// pieces of the session that are missing or corrupt, the client checks the data directory
+    /// again when it starts and downloads them again
+    pub missing_pieces: Vec<u32>,
+    /// bytes downloaded when the session was exported
+    pub downloaded: u64,
+    pub exported_at: u64,
+}
+
+fn pieces_dir(config: &Config, metainfo: &Metainfo) -> String {
+    format!("{}/{}/pieces", config.download_path, metainfo.info.name)
+}
+
+fn downloaded_bytes(metainfo: &Metainfo, pieces: &[u32]) -> u64 {
+    let downloaded = pieces.len() as u64 * metainfo.info.piece_length as u64;
+    downloaded.min(metainfo.info.length)
+}
+
+impl TorrentSession {
+    /// Captures the state of the torrent as it is on disk for the given config
+    pub fn export(torrent_path: &str, config: &Config) -> Result<TorrentSession, SessionError> {
+        let torrent = fs::read(torrent_path)?;
+        let metainfo = parse(&torrent)?;
+        let verified_pieces =
+            get_existing_pieces(metainfo.get_piece_count(), &pieces_dir(config, &metainfo));
+        let exported_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+
+        Ok(TorrentSession {
+            downloaded: downloaded_bytes(&metainfo, &verified_pieces),
+            torrent,
+            verified_pieces,
+            exported_at,
+            settings: config.portable_settings(),
+        })
+    }
+
+    /// Writes the torrent file and a config file pointing at the data directory of `config`
+    /// into `output_dir`. Every piece of the session is checked against its hash and the ones
+    /// that do not match are reported, the data directory is left untouched
+    pub fn import(
+        &self,
+        config: &Config,
+        output_dir: &str,
+    ) -> Result<ImportedSession, SessionError> {
+        let metainfo = parse(&self.torrent)?;
+        let pieces_dir = pieces_dir(config, &metainfo);
+        let (verified_pieces, missing_pieces): (Vec<u32>, Vec<u32>) =
+            self.verified_pieces.iter().partition(|piece| {
+                metainfo
+                    .info
+                    .pieces
+                    .get(**piece as usize)
+                    .map(|hash| piece_matches_hash(&format!("{}/{}", pieces_dir, piece), hash))
+                    .unwrap_or(false)
+            });
+        if !missing_pieces.is_empty() {
+            LOGGER.error(format!(
+                "{} pieces of the session are missing or corrupt in {}",
+                missing_pieces.len(),
+                pieces_dir
+            ));
+        }
+
+        fs::create_dir_all(output_dir)?;
+        let torrent_path = format!("{}/{}.torrent", output_dir, metainfo.info.name);
+        fs::write(&torrent_path, &self.torrent)?;
+        let config_path = format!("{}/{}.config.txt", output_dir, metainfo.info.name);
+        fs::write(&config_path, config.with_portable_settings(&self.settings))?;
+        LOGGER.info(format!(
+            "Imported session of {} exported at {} with {} verified pieces, {} of {} bytes were downloaded",
+            metainfo.info.name,
+            self.exported_at,
+            verified_pieces.len(),
+            self.downloaded,
+            metainfo.info.length
+        ));
+
+        Ok(ImportedSession {
+            torrent_path,
+            config_path,
+            verified_pieces,
+            missing_pieces,
+            downloaded: self.downloaded,
+            exported_at: self.exported_at,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SessionError> {
+        let metainfo = parse(&self.torrent)?;
+        let mut has_piece = vec![false; metainfo.get_piece_count() as usize];
+        for piece in &self.verified_pieces {
+            if let Some(has_piece) = has_piece.get_mut(*piece as usize) {
+                *has_piece = true;
+            }
+        }
+        let settings = self
+            .settings
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.as_bytes().to_vec(),
+                    BencodeDecodedValue::String(value.as_bytes().to_vec()),
+                )
+            })
+            .collect();
+
+        let session = HashMap::from([
+            (
+                VERSION.to_vec(),
+                BencodeDecodedValue::Integer(SESSION_VERSION),
+            ),
+            (
+                TORRENT.to_vec(),
+                BencodeDecodedValue::String(self.torrent.clone()),
+            ),
+            (
+                VERIFIED_PIECES.to_vec(),
+                BencodeDecodedValue::String(bitmap_from_pieces_vector(&has_piece)),
+            ),
+            (
+                DOWNLOADED.to_vec(),
+                BencodeDecodedValue::Integer(self.downloaded as i64),
+            ),
+            (
+                EXPORTED_AT.to_vec(),
+                BencodeDecodedValue::Integer(self.exported_at as i64),
+            ),
+            (SETTINGS.to_vec(), BencodeDecodedValue::Dictionary(settings)),
+        ]);
+        Ok(encode(&BencodeDecodedValue::Dictionary(session)))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TorrentSession, SessionError> {
+        let decoded = decode(bytes)?;
+        let session = decoded.get_as_dictionary()?;
+        let get = |key: &[u8]| {
+            session.get(key).ok_or_else(|| {
+                SessionError::InvalidSession(format!(
+                    "missing key: {}",
+                    String::from_utf8_lossy(key)
+                ))
+            })
+        };
+
+        let version = *get(VERSION)?.get_as_integer()?;
+        if version != SESSION_VERSION {
+            return Err(SessionError::InvalidSession(format!(
+                "unsupported version: {}",
+                version
+            )));
+        }
+        let mut verified_pieces = Bitfield::new();
+        verified_pieces.set_bitfield(get(VERIFIED_PIECES)?.get_as_string()?);
+        let mut settings = BTreeMap::new();
+        for (key, value) in get(SETTINGS)?.get_as_dictionary()? {
+            settings.insert(
+                String::from_utf8_lossy(key).to_string(),
+                String::from_utf8_lossy(value.get_as_string()?).to_string(),
+            );
+        }
+
+        Ok(TorrentSession {
+            torrent: get(TORRENT)?.get_as_string()?.to_vec(),
+            verified_pieces: verified_pieces
+                .iter_ones()
+                .map(|piece| piece as u32)
+                .collect(),
+            downloaded: *get(DOWNLOADED)?.get_as_integer()? as u64,
+            exported_at: *get(EXPORTED_AT)?.get_as_integer()? as u64,
+            settings,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), SessionError> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<TorrentSession, SessionError> {
+        TorrentSession::from_bytes(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer::sha1_of;
+
+    const TEST_DIR: &str = "src/session/test_files";
+
+    fn torrent_with_pieces(pieces: &[Vec<u8>]) -> Vec<u8> {
+        let info = HashMap::from([
+            (
+                b"name".to_vec(),
+                BencodeDecodedValue::String(b"session.txt".to_vec()),
+            ),
+            (b"piece length".to_vec(), BencodeDecodedValue::Integer(4)),
+            (
+                b"length".to_vec(),
+                BencodeDecodedValue::Integer(pieces.concat().len() as i64),
+            ),
+            (
+                b"pieces".to_vec(),
+                BencodeDecodedValue::String(
+                    pieces.iter().flat_map(|piece| sha1_of(piece)).collect(),
+                ),
+            ),
+        ]);
+        encode(&BencodeDecodedValue::Dictionary(HashMap::from([
+            (
+                b"announce".to_vec(),
+                BencodeDecodedValue::String(b"http://tracker/announce".to_vec()),
+            ),
+            (b"info".to_vec(), BencodeDecodedValue::Dictionary(info)),
+        ])))
+    }
+
+    #[test]
+    fn exports_and_imports_session() {
+        let pieces = vec![vec![1; 4], vec![2; 4], vec![3; 4]];
+        let config = Config::from_path(&format!("{}/session_config.txt", TEST_DIR)).unwrap();
+        let pieces_dir = format!("{}/session.txt/pieces", config.download_path);
+        fs::create_dir_all(&pieces_dir).unwrap();
+        fs::write(format!("{}/0", pieces_dir), &pieces[0]).unwrap();
+        fs::write(format!("{}/1", pieces_dir), &pieces[1]).unwrap();
+        let torrent_path = format!("{}/downloads/session.torrent", TEST_DIR);
+        fs::write(&torrent_path, torrent_with_pieces(&pieces)).unwrap();
+
+        let session = TorrentSession::export(&torrent_path, &config).unwrap();
+        assert_eq!(session.verified_pieces, vec![0, 1]);
+        assert_eq!(session.downloaded, 8);
+
+        let session_path = format!("{}/downloads/session.bin", TEST_DIR);
+        session.save(&session_path).unwrap();
+        let loaded = TorrentSession::load(&session_path).unwrap();
+        assert_eq!(loaded, session);
+
+        // the copy of piece 1 on the new machine is corrupt
+        fs::write(format!("{}/1", pieces_dir), [9; 4]).unwrap();
+        let output_dir = format!("{}/downloads/imported", TEST_DIR);
+        let imported = loaded.import(&config, &output_dir).unwrap();
+
+        assert_eq!(imported.verified_pieces, vec![0]);
+        assert_eq!(imported.missing_pieces, vec![1]);
+        assert_eq!(imported.downloaded, 8);
+        // the client checks the piece again when it starts
+        assert!(std::path::Path::new(&format!("{}/1", pieces_dir)).exists());
+        let imported_config = Config::from_path(&imported.config_path).unwrap();
+        assert_eq!(imported_config.listen_port, 4430);
+        assert_eq!(imported_config.dial_timeouts, config.dial_timeouts);
+        assert_eq!(imported_config.download_path, config.download_path);
+        assert!(Metainfo::from_torrent(&imported.torrent_path).is_ok());
+
+        fs::remove_dir_all(format!("{}/downloads", TEST_DIR)).unwrap();
+        fs::remove_dir_all(format!("{}/logs", TEST_DIR)).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_session_version() {
+        let session = encode(&BencodeDecodedValue::Dictionary(HashMap::from([(
+            VERSION.to_vec(),
+            BencodeDecodedValue::Integer(SESSION_VERSION + 1),
+        )])));
+        assert!(matches!(
+            TorrentSession::from_bytes(&session),
+            Err(SessionError::InvalidSession(_))
+        ));
+    }
+}
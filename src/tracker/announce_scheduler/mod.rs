@@ -0,0 +1,7 @@
+pub mod sender;
+pub mod types;
+pub mod worker;
+
+pub use sender::AnnounceSchedulerSender;
+pub use types::new_announce_scheduler;
+pub use worker::AnnounceSchedulerWorker;
@@ -0,0 +1,31 @@
+use crate::http::HttpsServiceError;
+use crate::tracker::announce_scheduler::types::AnnounceSchedulerMessage;
+use crate::tracker::RequestParameters;
+use std::sync::mpsc::{self, Sender};
+
+#[derive(Clone)]
+pub struct AnnounceSchedulerSender {
+    pub sender: Sender<AnnounceSchedulerMessage>,
+}
+
+impl AnnounceSchedulerSender {
+    pub fn stop(&self) {
+        let _ = self.sender.send(AnnounceSchedulerMessage::Stop);
+    }
+
+    /// Queues the announce and blocks until the tracker answers it
+    pub fn announce(
+        &self,
+        url: &str,
+        parameters: RequestParameters,
+    ) -> Result<Vec<u8>, HttpsServiceError> {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.sender.send(AnnounceSchedulerMessage::Announce(
+            url.to_string(),
+            parameters,
+            tx,
+        ));
+        rx.recv()
+            .map_err(|_| HttpsServiceError("Announce scheduler stopped".to_string()))?
+    }
+}
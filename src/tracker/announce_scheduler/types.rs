@@ -0,0 +1,42 @@
+use super::sender::types::AnnounceSchedulerSender;
+use super::worker::types::AnnounceSchedulerWorker;
+use crate::http::HttpsServiceError;
+use crate::tracker::RequestParameters;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+/// Minimum time between two announces sent to the same tracker host
+pub const ANNOUNCE_SPACING: Duration = Duration::from_secs(1);
+/// Maximum random delay added on top of the spacing, so torrents do not announce in bursts
+pub const MAX_ANNOUNCE_JITTER: Duration = Duration::from_secs(2);
+
+pub enum AnnounceSchedulerMessage {
+    /// Announce url, parameters and where to send the tracker response body to
+    Announce(
+        String,
+        RequestParameters,
+        Sender<Result<Vec<u8>, HttpsServiceError>>,
+    ),
+    Stop,
+}
+
+/// Creates a scheduler shared by every torrent. Announces are queued per tracker host and sent
+/// one at a time by a thread of that host, at least `min_spacing` plus a random jitter of up to
+/// `max_jitter` apart.
+pub fn new_announce_scheduler(
+    min_spacing: Duration,
+    max_jitter: Duration,
+) -> (AnnounceSchedulerSender, AnnounceSchedulerWorker) {
+    let (tx, rx) = mpsc::channel();
+
+    (
+        AnnounceSchedulerSender { sender: tx },
+        AnnounceSchedulerWorker {
+            receiver: rx,
+            hosts: HashMap::new(),
+            min_spacing,
+            max_jitter,
+        },
+    )
+}
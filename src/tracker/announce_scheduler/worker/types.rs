@@ -0,0 +1,399 @@
+use crate::http::{HttpsService, HttpsServiceError, IHttpService};
+use crate::logger::CustomLogger;
+use crate::tracker::announce_scheduler::types::AnnounceSchedulerMessage;
+use crate::tracker::constants::ANNOUNCE_PATH;
+use crate::tracker::utils::parameters_to_querystring;
+use crate::tracker::{Event, RequestParameters};
+use log::*;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, RecvError, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const LOGGER: CustomLogger = CustomLogger::init("Announce Scheduler");
+
+type AnnounceReply = Sender<Result<Vec<u8>, HttpsServiceError>>;
+
+struct PendingAnnounce {
+    url: String,
+    parameters: RequestParameters,
+    replies: Vec<AnnounceReply>,
+}
+
+impl PendingAnnounce {
+    // A newer announce of the same torrent replaces a queued one, unless it would lose an event
+    fn can_coalesce(&self, url: &str, parameters: &RequestParameters) -> bool {
+        self.url == url
+            && self.parameters.info_hash == parameters.info_hash
+            && (parameters.event == Event::KeepAlive || parameters.event == self.parameters.event)
+    }
+
+    fn coalesce(&mut self, parameters: RequestParameters, reply: AnnounceReply) {
+        self.parameters.peer_id = parameters.peer_id;
+        self.parameters.port = parameters.port;
+        self.parameters.uploaded = parameters.uploaded;
+        self.parameters.downloaded = parameters.downloaded;
+        self.parameters.left = parameters.left;
+        self.replies.push(reply);
+    }
+}
+
+/// Sends the announces to a single tracker host, spaced and reusing a keep alive connection.
+/// Each host has its own worker, so a host that hangs does not delay the others
+pub struct HostScheduleWorker {
+    receiver: Receiver<AnnounceSchedulerMessage>,
+    pending: VecDeque<PendingAnnounce>,
+    next_slot: Instant,
+    connection: Option<HttpsService>,
+    min_spacing: Duration,
+    max_jitter: Duration,
+}
+
+impl HostScheduleWorker {
+    fn schedule(&mut self, url: String, parameters: RequestParameters, reply: AnnounceReply) {
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.can_coalesce(&url, &parameters))
+        {
+            Some(pending) => {
+                trace!("Coalescing announce to {}", url);
+                pending.coalesce(parameters, reply);
+            }
+            None => self.pending.push_back(PendingAnnounce {
+                url,
+                parameters,
+                replies: vec![reply],
+            }),
+        }
+    }
+
+    fn handle_message(&mut self, message: AnnounceSchedulerMessage) -> bool {
+        match message {
+            AnnounceSchedulerMessage::Announce(url, parameters, reply) => {
+                self.schedule(url, parameters, reply);
+                true
+            }
+            AnnounceSchedulerMessage::Stop => false,
+        }
+    }
+
+    fn jitter(&self) -> Duration {
+        let max_jitter = self.max_jitter.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=max_jitter))
+    }
+
+    fn send_announce(&mut self, pending: PendingAnnounce) {
+        let query = parameters_to_querystring(&pending.parameters);
+        let response = match self.connection.as_mut() {
+            Some(connection) => connection.get(ANNOUNCE_PATH, &query),
+            None => HttpsService::keep_alive_from_url(&pending.url).and_then(|mut connection| {
+                let response = connection.get(ANNOUNCE_PATH, &query);
+                self.connection = Some(connection);
+                response
+            }),
+        };
+        if let Err(err) = &response {
+            LOGGER.error(format!("Announce to {} failed: {}", pending.url, err));
+            // start over with a new connection on the next announce
+            self.connection = None;
+        }
+        for reply in pending.replies {
+            let _ = reply.send(response.clone());
+        }
+    }
+
+    // Sends the next announce if its slot is due
+    fn dispatch_due_announce(&mut self) {
+        if self.next_slot > Instant::now() {
+            return;
+        }
+        if let Some(pending) = self.pending.pop_front() {
+            self.send_announce(pending);
+            self.next_slot = Instant::now() + self.min_spacing + self.jitter();
+        }
+    }
+
+    // Pending announces (stopped events among them) are sent right away when stopping
+    fn flush(&mut self) {
+        while let Some(pending) = self.pending.pop_front() {
+            self.send_announce(pending);
+        }
+    }
+
+    pub fn listen(&mut self) -> Result<(), RecvError> {
+        loop {
+            let message = if self.pending.is_empty() {
+                Some(self.receiver.recv()?)
+            } else {
+                match self
+                    .receiver
+                    .recv_timeout(self.next_slot.saturating_duration_since(Instant::now()))
+                {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return Err(RecvError),
+                }
+            };
+
+            let mut running = match message {
+                Some(message) => self.handle_message(message),
+                None => true,
+            };
+            // everything already queued is scheduled first, so it can be coalesced
+            while running {
+                match self.receiver.try_recv() {
+                    Ok(message) => running = self.handle_message(message),
+                    Err(_) => break,
+                }
+            }
+            if !running {
+                self.flush();
+                return Ok(());
+            }
+
+            self.dispatch_due_announce();
+        }
+    }
+}
+
+/// Channel and thread of the worker of a tracker host
+pub struct HostScheduleHandle {
+    sender: Sender<AnnounceSchedulerMessage>,
+    handle: JoinHandle<()>,
+}
+
+/// Sends the announces of every torrent, handing them to a worker for each tracker host
+pub struct AnnounceSchedulerWorker {
+    pub receiver: Receiver<AnnounceSchedulerMessage>,
+    pub hosts: HashMap<String, HostScheduleHandle>,
+    pub min_spacing: Duration,
+    pub max_jitter: Duration,
+}
+
+impl AnnounceSchedulerWorker {
+    fn start_host_worker(&self, host: &str) -> HostScheduleHandle {
+        trace!("Starting announce worker for host {}", host);
+        let (sender, receiver) = mpsc::channel();
+        let mut host_worker = HostScheduleWorker {
+            receiver,
+            pending: VecDeque::new(),
+            next_slot: Instant::now(),
+            connection: None,
+            min_spacing: self.min_spacing,
+            max_jitter: self.max_jitter,
+        };
+        let handle = std::thread::spawn(move || {
+            let _ = host_worker.listen();
+        });
+        HostScheduleHandle { sender, handle }
+    }
+
+    fn schedule(&mut self, url: String, parameters: RequestParameters, reply: AnnounceReply) {
+        let host = match HttpsService::url_to_host(&url) {
+            Ok(host) => host,
+            Err(err) => {
+                let _ = reply.send(Err(HttpsServiceError::from(err)));
+                return;
+            }
+        };
+        if !self.hosts.contains_key(&host) {
+            let host_worker = self.start_host_worker(&host);
+            self.hosts.insert(host.clone(), host_worker);
+        }
+        if let Some(host_worker) = self.hosts.get(&host) {
+            let _ = host_worker
+                .sender
+                .send(AnnounceSchedulerMessage::Announce(url, parameters, reply));
+        }
+    }
+
+    // Every host worker sends its pending announces before stopping
+    fn stop_host_workers(&mut self) {
+        for (_, host_worker) in self.hosts.drain() {
+            let _ = host_worker.sender.send(AnnounceSchedulerMessage::Stop);
+            let _ = host_worker.handle.join();
+        }
+    }
+
+    pub fn listen(&mut self) -> Result<(), RecvError> {
+        loop {
+            match self.receiver.recv()? {
+                AnnounceSchedulerMessage::Announce(url, parameters, reply) => {
+                    self.schedule(url, parameters, reply);
+                }
+                AnnounceSchedulerMessage::Stop => {
+                    LOGGER.info_str("Stopping Announce Scheduler");
+                    self.stop_host_workers();
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::announce_scheduler::new_announce_scheduler;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+
+    fn read_request(socket: &mut TcpStream) -> String {
+        let mut request = vec![];
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"\r\n\r\n") {
+            socket.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+        String::from_utf8(request).unwrap()
+    }
+
+    fn parameters(info_hash: u8, left: u32, event: Event) -> RequestParameters {
+        RequestParameters {
+            info_hash: vec![info_hash; 20],
+            peer_id: vec![0; 20],
+            port: 6881,
+            uploaded: 0,
+            downloaded: 0,
+            left,
+            event,
+        }
+    }
+
+    #[test]
+    fn test_coalesces_announces_and_reuses_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let server = std::thread::spawn(move || {
+            // a single connection serves every announce
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut requests = vec![];
+            for body in ["first", "other"] {
+                requests.push(read_request(&mut socket));
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n{}", body);
+                socket.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        let (sender, mut worker) = new_announce_scheduler(Duration::ZERO, Duration::ZERO);
+        let mut replies = vec![];
+        for announce in [
+            parameters(1, 100, Event::Started),
+            parameters(1, 50, Event::KeepAlive),
+            parameters(2, 10, Event::Started),
+        ] {
+            let (tx, rx) = mpsc::channel();
+            sender
+                .sender
+                .send(AnnounceSchedulerMessage::Announce(
+                    url.clone(),
+                    announce,
+                    tx,
+                ))
+                .unwrap();
+            replies.push(rx);
+        }
+        sender.stop();
+        worker.listen().unwrap();
+        let requests = server.join().unwrap();
+
+        let replies: Vec<Vec<u8>> = replies
+            .iter()
+            .map(|reply| reply.recv().unwrap().unwrap())
+            .collect();
+        assert_eq!(
+            replies,
+            vec![b"first".to_vec(), b"first".to_vec(), b"other".to_vec()]
+        );
+        assert_eq!(requests.len(), 2);
+        // the started event survives, with the counters of the newest announce
+        assert!(requests[0].contains("event=started"));
+        assert!(requests[0].contains("left=50"));
+        assert!(requests[1].contains("left=10"));
+    }
+
+    #[test]
+    fn test_spaces_announces_to_the_same_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut arrivals = vec![];
+            for _ in 0..2 {
+                read_request(&mut socket);
+                arrivals.push(Instant::now());
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+            arrivals
+        });
+
+        let spacing = Duration::from_millis(300);
+        let (sender, mut worker) = new_announce_scheduler(spacing, Duration::from_millis(50));
+        let handle = std::thread::spawn(move || worker.listen());
+        let other_sender = sender.clone();
+        let other_url = url.clone();
+        let other = std::thread::spawn(move || {
+            other_sender.announce(&other_url, parameters(2, 10, Event::Started))
+        });
+        let response = sender.announce(&url, parameters(1, 10, Event::Started));
+        let other_response = other.join().unwrap();
+        sender.stop();
+        handle.join().unwrap().unwrap();
+        let arrivals = server.join().unwrap();
+
+        assert_eq!(response.unwrap(), b"ok");
+        assert_eq!(other_response.unwrap(), b"ok");
+        assert!(arrivals[1].duration_since(arrivals[0]) >= spacing);
+    }
+
+    #[test]
+    fn test_a_hanging_host_does_not_delay_other_hosts() {
+        let hanging_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let hanging_url = format!(
+            "http://127.0.0.1:{}",
+            hanging_listener.local_addr().unwrap().port()
+        );
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let hanging_server = std::thread::spawn(move || {
+            // reads the announce and never answers it, until the test is over
+            let (mut socket, _) = hanging_listener.accept().unwrap();
+            read_request(&mut socket);
+            let _ = release_rx.recv();
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&mut socket);
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+        });
+
+        let (sender, mut worker) = new_announce_scheduler(Duration::ZERO, Duration::ZERO);
+        let handle = std::thread::spawn(move || worker.listen());
+        let hanging_sender = sender.clone();
+        let hanging = std::thread::spawn(move || {
+            hanging_sender.announce(&hanging_url, parameters(1, 10, Event::Started))
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        let started = Instant::now();
+        let response = sender.announce(&url, parameters(2, 10, Event::Started));
+
+        assert_eq!(response.unwrap(), b"ok");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        server.join().unwrap();
+        // closing the hanging host makes its pending announce fail
+        release_tx.send(()).unwrap();
+        hanging_server.join().unwrap();
+        assert!(hanging.join().unwrap().is_err());
+        sender.stop();
+        handle.join().unwrap().unwrap();
+    }
+}
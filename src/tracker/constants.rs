@@ -1,3 +1,4 @@
+pub const ANNOUNCE_PATH: &str = "/announce";
 pub const PEERS: &[u8] = b"peers";
 pub const INTERVAL: &[u8] = b"interval";
 pub const IP: &[u8] = b"ip";
@@ -1,5 +1,6 @@
 use crate::bencode::BencodeDecoderError;
 use crate::http::HttpsServiceError;
+use crate::json::JsonDecoderError;
 use std::fmt::Display;
 use std::fmt::Formatter;
 /// The error type that is returned when connecting to the tracker
@@ -9,6 +10,8 @@ pub enum TrackerError {
     BencodeError(String),
     /// Http connection failed
     HttpError(String),
+    /// The Json decoder failed to decode the response from a websocket tracker
+    JsonError(String),
     /// The tracker response was invalid
     InvalidResponse(String),
 }
@@ -19,6 +22,12 @@ impl From<BencodeDecoderError> for TrackerError {
     }
 }
 
+impl From<JsonDecoderError> for TrackerError {
+    fn from(error: JsonDecoderError) -> Self {
+        TrackerError::JsonError(error.to_string())
+    }
+}
+
 // impl from HttpConnectionError for TrackerError
 impl From<HttpsServiceError> for TrackerError {
     fn from(error: HttpsServiceError) -> Self {
@@ -34,6 +43,7 @@ impl Display for TrackerError {
             }
             TrackerError::HttpError(err) => write!(f, "Http error: {}", err),
             TrackerError::BencodeError(error) => write!(f, "Failed to parse bencode: {}", error),
+            TrackerError::JsonError(error) => write!(f, "Failed to parse json: {}", error),
         }
     }
 }
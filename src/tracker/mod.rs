@@ -1,10 +1,17 @@
+pub mod announce_scheduler;
 mod constants;
 mod errors;
+mod tracker_health;
 mod tracker_service;
 mod types;
 mod utils;
+mod websocket_tracker;
 
+pub use announce_scheduler::{
+    new_announce_scheduler, AnnounceSchedulerSender, AnnounceSchedulerWorker,
+};
 pub use errors::*;
+pub use tracker_health::{TrackerHealth, TrackerHealthTable};
 pub use tracker_service::ITrackerService;
 pub use tracker_service::MockTrackerService;
 pub use tracker_service::TrackerService;
@@ -0,0 +1,268 @@
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::time::Duration;
+
+/// Weight of the newest announce in the moving averages
+const SMOOTHING: f64 = 0.3;
+/// Time a failed announce is considered to cost, waiting for the tracker and trying the next one
+const FAILED_ANNOUNCE_COST: Duration = Duration::from_secs(30);
+/// Another tracker of the tier takes over once its score is below this share of the current one
+const SWITCH_RATIO: f64 = 0.5;
+
+/// Announce statistics of a tracker
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerHealth {
+    pub url: String,
+    /// announce-list tier the tracker belongs to, 0 being the first one
+    pub tier: usize,
+    pub announces: u32,
+    pub failures: u32,
+    /// moving average of the latency of successful announces
+    pub latency: Option<Duration>,
+    /// moving average of failed announces, between 0 and 1
+    pub failure_rate: f64,
+}
+
+impl TrackerHealth {
+    fn new(url: &str, tier: usize) -> Self {
+        TrackerHealth {
+            url: url.to_string(),
+            tier,
+            announces: 0,
+            failures: 0,
+            latency: None,
+            failure_rate: 0.0,
+        }
+    }
+
+    // Expected seconds an announce takes, lower is better. Trackers never used score best,
+    // so they get measured
+    fn score(&self) -> f64 {
+        let latency = self.latency.unwrap_or_default().as_secs_f64();
+        latency + self.failure_rate * FAILED_ANNOUNCE_COST.as_secs_f64()
+    }
+
+    fn record(&mut self, failed: bool) {
+        let outcome = if failed { 1.0 } else { 0.0 };
+        self.failure_rate = if self.announces == 0 {
+            outcome
+        } else {
+            SMOOTHING * outcome + (1.0 - SMOOTHING) * self.failure_rate
+        };
+        self.announces += 1;
+        if failed {
+            self.failures += 1;
+        }
+    }
+}
+
+/// Health of every tracker of a torrent, used to pick which one to announce to.
+/// Tiers are always tried in order and a tier is only left when all its trackers fail, as
+/// the announce-list rules say. Inside a tier, the trackers with the lowest latency and
+/// failure rate are tried first, except that the tracker that accepted the last announce
+/// keeps its place until it fails or another one scores clearly better. Trackers never used
+/// score best, so every tracker of a tier gets measured.
+#[derive(Debug, Clone)]
+pub struct TrackerHealthTable {
+    trackers: Vec<TrackerHealth>,
+    current: Option<String>,
+}
+
+impl TrackerHealthTable {
+    /// Without an announce-list, the announce url is the only tier. Trackers in a tier
+    /// are shuffled, so torrents sharing a tier spread their first announces
+    pub fn new(announce: &str, announce_list: &[Vec<String>]) -> Self {
+        let tiers = if announce_list.is_empty() {
+            vec![vec![announce.to_string()]]
+        } else {
+            announce_list.to_vec()
+        };
+        let mut trackers = vec![];
+        for (tier, mut urls) in tiers.into_iter().enumerate() {
+            urls.shuffle(&mut rand::thread_rng());
+            trackers.extend(urls.iter().map(|url| TrackerHealth::new(url, tier)));
+        }
+        TrackerHealthTable {
+            trackers,
+            current: None,
+        }
+    }
+
+    /// Urls in the order they should be tried for the next announce
+    pub fn announce_order(&self) -> Vec<String> {
+        let kept = self.kept_tracker();
+        let mut trackers: Vec<&TrackerHealth> = self.trackers.iter().collect();
+        trackers.sort_by(|a, b| {
+            a.tier
+                .cmp(&b.tier)
+                .then((Some(&b.url) == kept).cmp(&(Some(&a.url) == kept)))
+                .then(a.score().total_cmp(&b.score()))
+        });
+        trackers
+            .into_iter()
+            .map(|tracker| tracker.url.clone())
+            .collect()
+    }
+
+    pub fn record_success(&mut self, url: &str, latency: Duration) {
+        if let Some(tracker) = self.tracker_mut(url) {
+            tracker.latency = Some(match tracker.latency {
+                Some(average) => average.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING),
+                None => latency,
+            });
+            tracker.record(false);
+            self.current = Some(url.to_string());
+        }
+    }
+
+    pub fn record_failure(&mut self, url: &str) {
+        if let Some(tracker) = self.tracker_mut(url) {
+            tracker.record(true);
+        }
+        if self.current.as_deref() == Some(url) {
+            self.current = None;
+        }
+    }
+
+    /// Whether the tracker accepted an announce before, and so knows the session started
+    pub fn has_accepted_announce(&self, url: &str) -> bool {
+        self.trackers
+            .iter()
+            .any(|tracker| tracker.url == url && tracker.announces > tracker.failures)
+    }
+
+    pub fn trackers(&self) -> &[TrackerHealth] {
+        &self.trackers
+    }
+
+    // The tracker that accepted the last announce, unless another one of its tier is clearly better
+    fn kept_tracker(&self) -> Option<&String> {
+        let current = self
+            .trackers
+            .iter()
+            .find(|tracker| Some(&tracker.url) == self.current.as_ref())?;
+        let beaten = self.trackers.iter().any(|tracker| {
+            tracker.tier == current.tier && tracker.score() < current.score() * SWITCH_RATIO
+        });
+        if beaten {
+            None
+        } else {
+            Some(&current.url)
+        }
+    }
+
+    fn tracker_mut(&mut self, url: &str) -> Option<&mut TrackerHealth> {
+        self.trackers.iter_mut().find(|tracker| tracker.url == url)
+    }
+}
+
+impl fmt::Display for TrackerHealthTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "tier announces failures failure_rate latency url")?;
+        for tracker in &self.trackers {
+            let latency = match tracker.latency {
+                Some(latency) => format!("{}ms", latency.as_millis()),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "{:>4} {:>9} {:>8} {:>12.2} {:>7} {}",
+                tracker.tier,
+                tracker.announces,
+                tracker.failures,
+                tracker.failure_rate,
+                latency,
+                tracker.url
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiers() -> Vec<Vec<String>> {
+        vec![
+            vec!["http://a".to_string(), "http://b".to_string()],
+            vec!["http://c".to_string()],
+        ]
+    }
+
+    #[test]
+    fn uses_announce_without_announce_list() {
+        let table = TrackerHealthTable::new("http://announce", &[]);
+        assert_eq!(table.announce_order(), vec!["http://announce".to_string()]);
+    }
+
+    #[test]
+    fn prefers_the_fastest_tracker_of_the_first_tier() {
+        let mut table = TrackerHealthTable::new("http://a", &tiers());
+        table.record_success("http://a", Duration::from_millis(400));
+        table.record_success("http://b", Duration::from_millis(50));
+        table.record_success("http://c", Duration::from_millis(1));
+
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://b", "http://a", "http://c"]
+        );
+    }
+
+    #[test]
+    fn failing_trackers_are_tried_last_in_their_tier() {
+        let mut table = TrackerHealthTable::new("http://a", &tiers());
+        table.record_success("http://a", Duration::from_millis(400));
+        table.record_success("http://b", Duration::from_millis(50));
+        table.record_failure("http://b");
+        table.record_failure("http://b");
+
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://a", "http://b", "http://c"]
+        );
+        let b = &table
+            .trackers()
+            .iter()
+            .find(|t| t.url == "http://b")
+            .unwrap();
+        assert_eq!((b.announces, b.failures), (3, 2));
+    }
+
+    #[test]
+    fn keeps_the_tracker_that_accepted_the_last_announce() {
+        let mut table = TrackerHealthTable::new("http://a", &tiers());
+        table.record_success("http://b", Duration::from_millis(300));
+        table.record_success("http://a", Duration::from_millis(400));
+
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://a", "http://b", "http://c"]
+        );
+        assert!(!table.has_accepted_announce("http://c"));
+
+        table.record_failure("http://a");
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://b", "http://a", "http://c"]
+        );
+    }
+
+    #[test]
+    fn switches_to_a_clearly_better_tracker_of_the_tier() {
+        let mut table = TrackerHealthTable::new("http://a", &tiers());
+        table.record_success("http://a", Duration::from_millis(400));
+        // b was never used, so it is tried to get measured
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://b", "http://a", "http://c"]
+        );
+
+        table.record_success("http://b", Duration::from_millis(50));
+        table.record_success("http://a", Duration::from_millis(400));
+        assert_eq!(
+            table.announce_order(),
+            vec!["http://b", "http://a", "http://c"]
+        );
+    }
+}
@@ -1,9 +1,12 @@
+use super::announce_scheduler::AnnounceSchedulerSender;
 use super::constants::*;
 use super::errors::TrackerError;
+use super::tracker_health::{TrackerHealth, TrackerHealthTable};
 use super::types::RequestParameters;
 use super::types::TrackerResponse;
 use super::types::*;
 use super::utils::*;
+use super::websocket_tracker::announce_over_websocket;
 use crate::bencode::BencodeDecodedValue;
 use crate::bencode::*;
 use crate::client::ClientInfo;
@@ -15,7 +18,8 @@ use crate::peer::Peer;
 use log::*;
 use rand::Rng;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub trait ITrackerService: Clone {
     fn announce(&mut self, event: Option<Event>) -> Result<TrackerResponse, TrackerError>;
@@ -24,11 +28,75 @@ pub trait ITrackerService: Clone {
 #[derive(Clone)]
 pub struct TrackerService {
     client_info: ClientInfo,
+    announce_scheduler: Option<AnnounceSchedulerSender>,
+    // shared by every clone, so all announces of the torrent update it
+    tracker_health: Arc<Mutex<TrackerHealthTable>>,
 }
 
 impl TrackerService {
     pub fn new(client_info: ClientInfo) -> Self {
-        TrackerService { client_info }
+        let tracker_health = TrackerHealthTable::new(
+            &client_info.metainfo.announce,
+            &client_info.metainfo.announce_list,
+        );
+        TrackerService {
+            client_info,
+            announce_scheduler: None,
+            tracker_health: Arc::new(Mutex::new(tracker_health)),
+        }
+    }
+
+    /// Http announces go through the scheduler shared with the other torrents instead of
+    /// opening a connection each
+    pub fn with_scheduler(
+        client_info: ClientInfo,
+        announce_scheduler: AnnounceSchedulerSender,
+    ) -> Self {
+        TrackerService {
+            announce_scheduler: Some(announce_scheduler),
+            ..TrackerService::new(client_info)
+        }
+    }
+
+    /// Announce statistics of every tracker of the torrent
+    pub fn tracker_health(&self) -> Vec<TrackerHealth> {
+        self.lock_tracker_health().trackers().to_vec()
+    }
+
+    fn lock_tracker_health(&self) -> std::sync::MutexGuard<'_, TrackerHealthTable> {
+        self.tracker_health
+            .lock()
+            .expect("should be able to lock tracker health")
+    }
+
+    fn announce_to(
+        &self,
+        announce_url: &str,
+        request_parameters: RequestParameters,
+    ) -> Result<TrackerResponse, TrackerError> {
+        if announce_url.starts_with("wss://") || announce_url.starts_with("ws://") {
+            return announce_over_websocket(
+                announce_url,
+                &request_parameters,
+                &self.client_info.config.dial_timeouts,
+            );
+        }
+
+        let response: Vec<u8> = match &self.announce_scheduler {
+            Some(announce_scheduler) => {
+                announce_scheduler.announce(announce_url, request_parameters)?
+            }
+            None => {
+                let mut http_service = HttpsService::from_url(announce_url)?;
+                http_service.get(
+                    ANNOUNCE_PATH,
+                    &parameters_to_querystring(&request_parameters),
+                )?
+            }
+        };
+        debug!("parsing tracker response");
+
+        self.parse_response(decode(&response)?)
     }
 
     fn parse_response(
@@ -114,7 +182,7 @@ impl TrackerService {
                     )))
                 }
             };
-            println!("{:?}", peer_dic.get(PORT));
+            debug!("peer port: {:?}", peer_dic.get(PORT));
             let port = match peer_dic.get(PORT) {
                 Some(port) => *port.get_as_integer()? as u16,
                 None => {
@@ -182,7 +250,6 @@ impl TrackerService {
 impl ITrackerService for TrackerService {
     fn announce(&mut self, event: Option<Event>) -> Result<TrackerResponse, TrackerError> {
         debug!("Sending tracker announce request");
-        let mut http_service = HttpsService::from_url(&self.client_info.metainfo.announce)?;
         let pieces_dir = format!(
             "{}/{}/pieces",
             self.client_info.config.download_path, self.client_info.metainfo.info.name
@@ -212,14 +279,37 @@ impl ITrackerService for TrackerService {
             event: event.unwrap_or(Event::KeepAlive),
         };
 
-        let response: Vec<u8> =
-            http_service.get("/announce", &parameters_to_querystring(&request_parameters))?;
-        debug!("parsing tracker response");
-
-        match self.parse_response(decode(&response)?) {
-            Ok(tracker_response) => Ok(tracker_response),
-            Err(err) => Err(err),
+        let mut last_error = None;
+        let announce_order = self.lock_tracker_health().announce_order();
+        for announce_url in announce_order {
+            let mut request_parameters = request_parameters.clone();
+            // a tracker first used after a failure has not seen the session start
+            if request_parameters.event == Event::KeepAlive
+                && !self
+                    .lock_tracker_health()
+                    .has_accepted_announce(&announce_url)
+            {
+                request_parameters.event = Event::Started;
+            }
+            let start = Instant::now();
+            match self.announce_to(&announce_url, request_parameters) {
+                Ok(tracker_response) => {
+                    let mut tracker_health = self.lock_tracker_health();
+                    tracker_health.record_success(&announce_url, start.elapsed());
+                    debug!("Tracker health:\n{}", tracker_health);
+                    return Ok(tracker_response);
+                }
+                Err(err) => {
+                    warn!("Announce to {} failed: {}", announce_url, err);
+                    self.lock_tracker_health().record_failure(&announce_url);
+                    last_error = Some(err);
+                }
+            }
         }
+
+        Err(last_error.unwrap_or_else(|| {
+            TrackerError::InvalidResponse("torrent has no trackers".to_string())
+        }))
     }
 }
 
@@ -1,7 +1,7 @@
 use crate::peer::Peer;
 use std::time::Duration;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub enum Event {
     Started,
     Completed,
@@ -20,6 +20,7 @@ impl Event {
     }
 }
 
+#[derive(Clone)]
 pub struct RequestParameters {
     pub info_hash: Vec<u8>,
     pub peer_id: Vec<u8>,
@@ -1,7 +1,7 @@
 use super::types::RequestParameters;
 use super::Event;
 use std::collections::HashMap;
-const WANTED_CONNECTIONS: u32 = 100;
+pub const WANTED_CONNECTIONS: u32 = 100;
 
 // Transforms a slice of bytes into an url-encoded String
 fn to_urlencoded(bytes: &[u8]) -> String {
@@ -0,0 +1,267 @@
+use super::errors::TrackerError;
+use super::types::{RequestParameters, TrackerResponse};
+use super::utils::WANTED_CONNECTIONS;
+use super::Event;
+use crate::http::{HttpsService, HttpsServiceError, WebSocketMessage, WebSocketStream};
+use crate::json::{self, JsonValue};
+use crate::peer::peer_message_service_provider;
+use crate::peer::{DialTimeouts, Peer};
+use log::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const ACTION: &str = "action";
+const ANNOUNCE: &str = "announce";
+const INFO_HASH: &str = "info_hash";
+const PEER_ID: &str = "peer_id";
+const INTERVAL: &str = "interval";
+const PEERS: &str = "peers";
+const IP: &str = "ip";
+const PORT: &str = "port";
+const FAILURE_REASON: &str = "failure reason";
+// offers and answers for WebRTC peers can arrive before the announce response
+const MAX_MESSAGES_BEFORE_RESPONSE: usize = 10;
+
+/// Announces to a WebTorrent style tracker (`ws://` or `wss://` url), which talks json over a websocket.
+/// The connect timeout depends on the tracker host, as it does when dialing peers
+pub fn announce_over_websocket(
+    url: &str,
+    request_parameters: &RequestParameters,
+    dial_timeouts: &DialTimeouts,
+) -> Result<TrackerResponse, TrackerError> {
+    let host = HttpsService::url_to_host(url).map_err(HttpsServiceError::from)?;
+    let connect_timeout = dial_timeouts.for_ip(&HttpsService::remove_port_from_host(&host));
+    let mut websocket = WebSocketStream::connect(url, Some(connect_timeout))?;
+    websocket.send_text(&announce_message(request_parameters))?;
+
+    for _ in 0..MAX_MESSAGES_BEFORE_RESPONSE {
+        let message = match websocket.read_message()? {
+            WebSocketMessage::Text(text) => text,
+            WebSocketMessage::Binary(_) => continue,
+        };
+        if let Some(response) =
+            parse_announce_response(&json::decode(&message)?, &request_parameters.info_hash)?
+        {
+            websocket.close();
+            return Ok(response);
+        }
+        trace!("Ignoring websocket tracker message: {}", message);
+    }
+    websocket.close();
+    Err(TrackerError::InvalidResponse(
+        "websocket tracker did not answer the announce".to_string(),
+    ))
+}
+
+// WebTorrent trackers send binary values as strings with one char per byte
+fn to_binary_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| char::from(*byte)).collect()
+}
+
+fn from_binary_string(string: &str) -> Option<Vec<u8>> {
+    string.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+fn announce_message(parameters: &RequestParameters) -> String {
+    let mut message = HashMap::new();
+    message.insert(ACTION.to_string(), JsonValue::String(ANNOUNCE.to_string()));
+    message.insert(
+        INFO_HASH.to_string(),
+        JsonValue::String(to_binary_string(&parameters.info_hash)),
+    );
+    message.insert(
+        PEER_ID.to_string(),
+        JsonValue::String(to_binary_string(&parameters.peer_id)),
+    );
+    message.insert(
+        "uploaded".to_string(),
+        JsonValue::Integer(parameters.uploaded as i64),
+    );
+    message.insert(
+        "downloaded".to_string(),
+        JsonValue::Integer(parameters.downloaded as i64),
+    );
+    message.insert(
+        "left".to_string(),
+        JsonValue::Integer(parameters.left as i64),
+    );
+    message.insert(
+        "numwant".to_string(),
+        JsonValue::Integer(WANTED_CONNECTIONS as i64),
+    );
+    message.insert("offers".to_string(), JsonValue::Array(vec![]));
+    if parameters.event != Event::KeepAlive {
+        message.insert(
+            "event".to_string(),
+            JsonValue::String(parameters.event.as_string()),
+        );
+    }
+    json::encode(&JsonValue::Object(message))
+}
+
+// Returns None for the messages that are not the response to our announce
+fn parse_announce_response(
+    message: &JsonValue,
+    info_hash: &[u8],
+) -> Result<Option<TrackerResponse>, TrackerError> {
+    let message = message.get_as_object()?;
+    if let Some(reason) = message.get(FAILURE_REASON) {
+        return Err(TrackerError::InvalidResponse(
+            reason.get_as_string()?.to_string(),
+        ));
+    }
+    let is_announce =
+        matches!(message.get(ACTION), Some(JsonValue::String(action)) if action == ANNOUNCE);
+    let is_our_torrent = match message.get(INFO_HASH) {
+        Some(JsonValue::String(hash)) => from_binary_string(hash).as_deref() == Some(info_hash),
+        _ => true,
+    };
+    if !is_announce
+        || !is_our_torrent
+        || message.contains_key("offer")
+        || message.contains_key("answer")
+    {
+        return Ok(None);
+    }
+
+    let interval = match message.get(INTERVAL) {
+        Some(JsonValue::Integer(interval)) if *interval > 0 => {
+            Some(Duration::from_secs(*interval as u64))
+        }
+        Some(JsonValue::Integer(interval)) => {
+            return Err(TrackerError::InvalidResponse(format!(
+                "invalid interval {}",
+                interval
+            )))
+        }
+        _ => None,
+    };
+    let peers = match message.get(PEERS) {
+        Some(peers) => peers
+            .get_as_array()?
+            .iter()
+            .map(build_peer)
+            .collect::<Result<Vec<Peer>, TrackerError>>()?,
+        None => vec![],
+    };
+    Ok(Some(TrackerResponse { peers, interval }))
+}
+
+fn build_peer(value: &JsonValue) -> Result<Peer, TrackerError> {
+    let peer = value.get_as_object()?;
+    let ip = peer
+        .get(IP)
+        .ok_or_else(|| TrackerError::InvalidResponse(format!("missing ip of peer {:?}", peer)))?
+        .get_as_string()?;
+    let port = *peer
+        .get(PORT)
+        .ok_or_else(|| TrackerError::InvalidResponse(format!("missing port of peer {:?}", peer)))?
+        .get_as_integer()?;
+    let port = u16::try_from(port)
+        .map_err(|_| TrackerError::InvalidResponse(format!("invalid peer port: {}", port)))?;
+    let peer_id = match peer.get(PEER_ID) {
+        Some(JsonValue::String(peer_id)) => from_binary_string(peer_id).ok_or_else(|| {
+            TrackerError::InvalidResponse(format!("invalid peer id: {}", peer_id))
+        })?,
+        // we create a random peer id if they don't provide one
+        _ => rand::thread_rng().gen::<[u8; 20]>().to_vec(),
+    };
+
+    Ok(Peer {
+        ip: ip.to_string(),
+        port,
+        peer_id,
+        peer_message_service_provider,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_parameters() -> RequestParameters {
+        RequestParameters {
+            info_hash: vec![0, 1, 0x7f, 0x80, 0xff],
+            peer_id: b"-RS0001-123456789012".to_vec(),
+            port: 6881,
+            uploaded: 0,
+            downloaded: 10,
+            left: 20,
+            event: Event::Started,
+        }
+    }
+
+    #[test]
+    fn announce_message_uses_binary_strings() {
+        let parameters = request_parameters();
+        let message = json::decode(&announce_message(&parameters)).unwrap();
+        let message = message.get_as_object().unwrap();
+
+        assert_eq!(message[ACTION], JsonValue::String(ANNOUNCE.to_string()));
+        assert_eq!(
+            from_binary_string(message[INFO_HASH].get_as_string().unwrap()).unwrap(),
+            parameters.info_hash
+        );
+        assert_eq!(message["left"], JsonValue::Integer(20));
+        assert_eq!(message["event"], JsonValue::String("started".to_string()));
+    }
+
+    #[test]
+    fn parses_announce_response_with_peers() {
+        let info_hash = request_parameters().info_hash;
+        let message = format!(
+            r#"{{"action":"announce","interval":120,"info_hash":{},"peers":[{{"ip":"1.2.3.4","port":6881,"peer_id":"abcdefghijklmnopqrst"}},{{"ip":"5.6.7.8","port":51413}}]}}"#,
+            json::encode(&JsonValue::String(to_binary_string(&info_hash)))
+        );
+
+        let response = parse_announce_response(&json::decode(&message).unwrap(), &info_hash)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(response.interval, Some(Duration::from_secs(120)));
+        assert_eq!(response.peers.len(), 2);
+        assert_eq!(response.peers[0].ip, "1.2.3.4");
+        assert_eq!(response.peers[0].peer_id, b"abcdefghijklmnopqrst".to_vec());
+        assert_eq!(response.peers[1].port, 51413);
+        assert_eq!(response.peers[1].peer_id.len(), 20);
+    }
+
+    #[test]
+    fn ignores_offers_and_other_torrents() {
+        let info_hash = request_parameters().info_hash;
+        let offer = r#"{"action":"announce","offer":{"type":"offer","sdp":""},"offer_id":"x"}"#;
+        let other_torrent = r#"{"action":"announce","info_hash":"aaaaa","interval":120}"#;
+
+        assert!(
+            parse_announce_response(&json::decode(offer).unwrap(), &info_hash)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            parse_announce_response(&json::decode(other_torrent).unwrap(), &info_hash)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn non_positive_interval_is_an_error() {
+        for interval in [0, -1] {
+            let message = format!(r#"{{"action":"announce","interval":{}}}"#, interval);
+            assert!(matches!(
+                parse_announce_response(&json::decode(&message).unwrap(), &[]),
+                Err(TrackerError::InvalidResponse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn failure_reason_is_an_error() {
+        let message = r#"{"action":"announce","failure reason":"invalid info hash"}"#;
+        assert!(matches!(
+            parse_announce_response(&json::decode(message).unwrap(), &[]),
+            Err(TrackerError::InvalidResponse(reason)) if reason == "invalid info hash"
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+use super::state::TuiState;
+use super::view;
+use crate::event_bus::{EventBusSender, UIMessage};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// how long to wait for a key before checking for new messages
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs the terminal UI until the user quits, it draws the state received from the event bus
+pub fn run_tui(event_bus_sender: EventBusSender) -> io::Result<()> {
+    // the first message received is a snapshot with everything that happened before the tui started
+    let (tx_messages, rx_messages) = mpsc::channel::<UIMessage>();
+    event_bus_sender.subscribe(tx_messages);
+
+    let mut terminal = ratatui::init();
+    let mut state = TuiState::default();
+    let result = loop {
+        while let Ok(message) = rx_messages.try_recv() {
+            state.update(message);
+        }
+        if let Err(err) = terminal.draw(|frame| view::draw(frame, &state)) {
+            break Err(err);
+        }
+        match handle_input(&mut state) {
+            Ok(true) => break Ok(()),
+            Ok(false) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+// returns true once the user asked to quit
+fn handle_input(state: &mut TuiState) -> io::Result<bool> {
+    if !event::poll(POLL_INTERVAL)? {
+        return Ok(false);
+    }
+    if let Event::Key(key) = event::read()? {
+        if key.kind != KeyEventKind::Press {
+            return Ok(false);
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            _ => {}
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,6 @@
+mod app;
+mod state;
+mod view;
+
+pub use app::run_tui;
+pub use state::TuiState;
@@ -0,0 +1,133 @@
+use crate::event_bus::{PeerSnapshot, TorrentSnapshot, UIMessage, UISnapshot};
+
+/// What the terminal UI shows: the state of every torrent and peer, and the selected torrent
+#[derive(Default)]
+pub struct TuiState {
+    snapshot: UISnapshot,
+    selected: usize,
+}
+
+impl TuiState {
+    /// Updates the state with a message from the event bus, the first one is always a snapshot
+    pub fn update(&mut self, message: UIMessage) {
+        match message {
+            UIMessage::Snapshot(snapshot) => self.snapshot = snapshot,
+            message => self.snapshot.apply(&message),
+        }
+        self.selected = self
+            .selected
+            .min(self.snapshot.torrents.len().saturating_sub(1));
+    }
+
+    pub fn torrents(&self) -> &[TorrentSnapshot] {
+        &self.snapshot.torrents
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        if self.snapshot.torrents.is_empty() {
+            None
+        } else {
+            Some(self.selected)
+        }
+    }
+
+    pub fn selected_torrent(&self) -> Option<&TorrentSnapshot> {
+        self.snapshot.torrents.get(self.selected)
+    }
+
+    /// Peers of the selected torrent, the connected ones first
+    pub fn selected_peers(&self) -> Vec<&PeerSnapshot> {
+        let torrent_name = match self.selected_torrent() {
+            Some(torrent) => &torrent.metainfo.info.name,
+            None => return vec![],
+        };
+        let mut peers: Vec<&PeerSnapshot> = self
+            .snapshot
+            .peers
+            .iter()
+            .filter(|peer| &peer.statistics.torrentname == torrent_name)
+            .collect();
+        peers.sort_by_key(|peer| !peer.is_connected);
+        peers
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.snapshot.torrents.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_bus::PeerStatistics;
+    use crate::metainfo::{Info, Metainfo};
+    use crate::peer::{PeerConnectionState, PeerState};
+
+    fn metainfo(name: &str) -> Metainfo {
+        Metainfo {
+            announce: "".to_string(),
+            announce_list: vec![],
+            info: Info {
+                piece_length: 8,
+                pieces: vec![vec![0; 20]; 3],
+                length: 24,
+                name: name.to_string(),
+                files: None,
+            },
+            info_hash: vec![],
+        }
+    }
+
+    fn peer_statistics(torrent_name: &str, peer_id: Vec<u8>) -> PeerStatistics {
+        let state = PeerState {
+            chocked: true,
+            interested: false,
+        };
+        PeerStatistics {
+            torrentname: torrent_name.to_string(),
+            peerid: peer_id,
+            ip: "127.0.0.1".to_string(),
+            port: 6881,
+            state: PeerConnectionState {
+                client: state.clone(),
+                peer: state,
+            },
+            downloadrate: 0,
+            uploadrate: 0,
+        }
+    }
+
+    #[test]
+    fn shows_the_peers_of_the_selected_torrent() {
+        let mut snapshot = UISnapshot::default();
+        snapshot.apply(&UIMessage::AddTorrent(metainfo("debian")));
+        let mut state = TuiState::default();
+        state.update(UIMessage::Snapshot(snapshot));
+        state.update(UIMessage::AddTorrent(metainfo("ubuntu")));
+        state.update(UIMessage::AddPeerStatistics(peer_statistics("debian", vec![1])));
+        state.update(UIMessage::AddPeerStatistics(peer_statistics("ubuntu", vec![2])));
+        state.update(UIMessage::AddPeerStatistics(peer_statistics("ubuntu", vec![3])));
+        state.update(UIMessage::ClosedConnection("ubuntu".to_string(), vec![2]));
+
+        assert_eq!(state.selected_peers().len(), 1);
+        state.select_next();
+        state.select_next();
+        assert_eq!(state.selected_index(), Some(1));
+        let peer_ids: Vec<&Vec<u8>> = state
+            .selected_peers()
+            .iter()
+            .map(|peer| &peer.statistics.peerid)
+            .collect();
+        assert_eq!(peer_ids, vec![&vec![3], &vec![2]]);
+
+        state.update(UIMessage::Snapshot(UISnapshot::default()));
+        assert_eq!(state.selected_index(), None);
+        assert!(state.selected_peers().is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use super::state::TuiState;
+use crate::event_bus::{PeerSnapshot, TorrentSnapshot};
+use crate::peer::PeerState;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+
+const HELP: &str = " ↑/k ↓/j: select torrent   q/Esc: close, downloads keep running ";
+
+/// Draws the torrent list, the details of the selected torrent and its peers
+pub fn draw(frame: &mut Frame, state: &TuiState) {
+    let [torrents_area, details_area, peers_area] = Layout::vertical([
+        Constraint::Percentage(30),
+        Constraint::Length(8),
+        Constraint::Min(5),
+    ])
+    .areas(frame.area());
+
+    let mut table_state = TableState::default().with_selected(state.selected_index());
+    frame.render_stateful_widget(torrent_list(state), torrents_area, &mut table_state);
+    frame.render_widget(torrent_details(state.selected_torrent()), details_area);
+    frame.render_widget(peer_list(&state.selected_peers()), peers_area);
+}
+
+fn completion_percentage(torrent: &TorrentSnapshot) -> f64 {
+    let piece_count = torrent.metainfo.get_piece_count();
+    if piece_count == 0 {
+        return 100f64;
+    }
+    f64::from(torrent.downloaded_pieces) * 100f64 / f64::from(piece_count)
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn bytesps_to_mbps(bps: f32) -> f32 {
+    bps / 125000f32
+}
+
+fn peer_state_to_string(state: &PeerState) -> String {
+    let choked = if state.chocked { "choked" } else { "unchoked" };
+    let interested = if state.interested {
+        "interested"
+    } else {
+        "not interested"
+    };
+    format!("{}, {}", choked, interested)
+}
+
+fn torrent_list(state: &TuiState) -> Table<'static> {
+    let rows = state.torrents().iter().map(|torrent| {
+        Row::new(vec![
+            torrent.metainfo.info.name.clone(),
+            format!("{:.1}%", completion_percentage(torrent)),
+            format!(
+                "{}/{}",
+                torrent.downloaded_pieces,
+                torrent.metainfo.get_piece_count()
+            ),
+            torrent.peer_count.to_string(),
+            torrent.active_connections.to_string(),
+        ])
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(8),
+            Constraint::Length(13),
+            Constraint::Length(6),
+            Constraint::Length(11),
+        ],
+    )
+    .header(
+        Row::new(vec!["Name", "Done", "Pieces", "Peers", "Connections"])
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+    )
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Torrents ")
+            .title_bottom(HELP),
+    )
+}
+
+fn torrent_details(torrent: Option<&TorrentSnapshot>) -> Paragraph<'static> {
+    let block = Block::default().borders(Borders::ALL).title(" Details ");
+    let torrent = match torrent {
+        Some(torrent) => torrent,
+        None => return Paragraph::new("Waiting for torrents...").block(block),
+    };
+    let metainfo = &torrent.metainfo;
+    let lines = vec![
+        Line::from(format!("Name: {}", metainfo.info.name)),
+        Line::from(format!("Info hash: {}", bytes_to_hex(&metainfo.info_hash))),
+        Line::from(format!(
+            "Size: {} MB in {} pieces of {} KB",
+            metainfo.info.length / 1024 / 1024,
+            metainfo.get_piece_count(),
+            metainfo.info.piece_length / 1024
+        )),
+        Line::from(format!("Tracker: {}", metainfo.announce)),
+        Line::from(format!(
+            "Downloaded: {} pieces ({:.1}%)",
+            torrent.downloaded_pieces,
+            completion_percentage(torrent)
+        )),
+        Line::from(format!(
+            "Peers: {} received, {} connected",
+            torrent.peer_count, torrent.active_connections
+        )),
+    ];
+    Paragraph::new(lines).block(block)
+}
+
+fn peer_list(peers: &[&PeerSnapshot]) -> Table<'static> {
+    let rows = peers.iter().map(|peer| {
+        let statistics = &peer.statistics;
+        let row = Row::new(vec![
+            format!("{}:{}", statistics.ip, statistics.port),
+            peer_state_to_string(&statistics.state.client),
+            peer_state_to_string(&statistics.state.peer),
+            format!("{:.2}", bytesps_to_mbps(peer.download_rate)),
+            format!("{:.2}", bytesps_to_mbps(peer.upload_rate)),
+            peer.downloaded_pieces.to_string(),
+        ]);
+        if peer.is_connected {
+            row
+        } else {
+            row.style(Style::new().add_modifier(Modifier::DIM))
+        }
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Length(22),
+            Constraint::Length(25),
+            Constraint::Length(25),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(7),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Address",
+            "Client",
+            "Peer",
+            "Down (Mbps)",
+            "Up (Mbps)",
+            "Pieces",
+        ])
+        .style(Style::new().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(" Peers "))
+}
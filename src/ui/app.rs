@@ -1,5 +1,5 @@
 use super::Notebook;
-use super::UIMessage;
+use crate::event_bus::{EventBusSender, IUIMessageSubscriber, UIMessage};
 use glib::{Continue, PRIORITY_DEFAULT};
 use gtk::gdk_pixbuf::PixbufLoader;
 use gtk::prelude::*;
@@ -8,7 +8,6 @@ use gtk::{Application, ApplicationWindow};
 use log::*;
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::sync::mpsc::Sender;
 
 // struct GeneralTorrentInformation {
 //     name: String,
@@ -25,20 +24,26 @@ use std::sync::mpsc::Sender;
 //     peerStatistics: Vec<PeerStatistics>,
 // }
 
-pub fn run_ui(client_sender: Sender<glib::Sender<UIMessage>>) {
+impl IUIMessageSubscriber for glib::Sender<UIMessage> {
+    fn notify(&self, message: UIMessage) -> bool {
+        self.send(message).is_ok()
+    }
+}
+
+pub fn run_ui(event_bus_sender: EventBusSender) {
     let app = Application::builder()
         .application_id("org.gtk-rs.bittorrent")
         .build();
 
     app.connect_activate(move |app| {
-        build_ui(app, &client_sender);
+        build_ui(app, &event_bus_sender);
     });
 
     let args: Vec<String> = vec![]; // necessary to not use main program args
     app.run_with_args(&args);
 }
 
-fn build_ui(app: &Application, client_sender: &Sender<glib::Sender<UIMessage>>) {
+fn build_ui(app: &Application, event_bus_sender: &EventBusSender) {
     // Create a window
     let window = ApplicationWindow::builder()
         .application(app)
@@ -61,10 +66,9 @@ fn build_ui(app: &Application, client_sender: &Sender<glib::Sender<UIMessage>>)
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 
+    // the first message received is a snapshot with everything that happened before this window
     let (tx_messages, rx_messages) = glib::MainContext::channel(PRIORITY_DEFAULT);
-    client_sender
-        .send(tx_messages)
-        .expect("could not send sender to client");
+    event_bus_sender.subscribe(tx_messages);
 
     let notebook = Rc::new(RefCell::new(Notebook::new(&window)));
 
@@ -34,11 +34,17 @@ impl Model {
     }
 
     // apply closure to the item which has same torrent name as parameter
-    pub fn edit(&self, peer_id: &[u8], f: impl Fn(&mut DownloadStatistics)) {
+    // a peer can serve several torrents, so rows are identified by the torrent and the peer id
+    fn is_row_of(item: &DownloadStatistics, torrent_name: &str, peer_id: &[u8]) -> bool {
+        item.property::<String>("torrentname") == torrent_name
+            && item.property::<String>("id") == DownloadStatistics::sha1_of(peer_id)
+    }
+
+    pub fn edit(&self, torrent_name: &str, peer_id: &[u8], f: impl Fn(&mut DownloadStatistics)) {
         let imp = self.imp();
         let mut data = imp.0.borrow_mut();
         for item in data.iter_mut() {
-            if item.property::<String>("id") == DownloadStatistics::sha1_of(peer_id) {
+            if Self::is_row_of(item, torrent_name, peer_id) {
                 f(item);
             }
         }
@@ -92,7 +98,12 @@ impl Model {
         }
     }
 
-    pub fn edit_state(&self, peer_id: &[u8], peer_conn_state: PeerConnectionState) {
+    pub fn edit_state(
+        &self,
+        torrent_name: &str,
+        peer_id: &[u8],
+        peer_conn_state: PeerConnectionState,
+    ) {
         let _client_interested = match peer_conn_state.client.interested {
             true => "interested",
             false => "not interested",
@@ -115,7 +126,7 @@ impl Model {
         let imp = self.imp();
         let mut data = imp.0.borrow_mut();
         for item in data.iter_mut() {
-            if item.property::<String>("id") == DownloadStatistics::sha1_of(peer_id) {
+            if Self::is_row_of(item, torrent_name, peer_id) {
                 item.set_property("clientstate", &peer_state);
             }
         }
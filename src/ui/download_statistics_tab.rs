@@ -2,8 +2,7 @@ use crate::peer::PeerConnectionState;
 
 use super::download_statistics_model::Model;
 use super::download_statistics_row::DownloadStatistics;
-use super::messages::PeerStatistics;
-use super::UIMessage;
+use crate::event_bus::{PeerStatistics, UIMessage, UISnapshot};
 use gtk::{self};
 use gtk::{
     glib::{self, clone},
@@ -199,17 +198,22 @@ impl DownloadStatisticsTab {
 
     fn update_download_rate(
         &self,
+        torrent_name: &str,
         rate: f32,
         peer_id: &[u8],
     ) -> Result<(), DownloadStatisticsTabError> {
-        self.model.edit(peer_id, |item| {
+        self.model.edit(torrent_name, peer_id, |item| {
             item.set_property("downloadrate", &self.bytesps_to_mbps(rate));
         });
         Ok(())
     }
 
-    fn update_downloaded_pieces(&self, peer_id: &[u8]) -> Result<(), DownloadStatisticsTabError> {
-        self.model.edit(peer_id, |item| {
+    fn update_downloaded_pieces(
+        &self,
+        torrent_name: &str,
+        peer_id: &[u8],
+    ) -> Result<(), DownloadStatisticsTabError> {
+        self.model.edit(torrent_name, peer_id, |item| {
             let downloaded_pieces = item.property::<u32>("downloadedpieces") + 1;
             item.set_property("downloadedpieces", &downloaded_pieces);
         });
@@ -218,10 +222,11 @@ impl DownloadStatisticsTab {
 
     fn update_upload_rate(
         &self,
+        torrent_name: &str,
         rate: f32,
         peer_id: &[u8],
     ) -> Result<(), DownloadStatisticsTabError> {
-        self.model.edit(peer_id, |item| {
+        self.model.edit(torrent_name, peer_id, |item| {
             item.set_property("uploadrate", &self.bytesps_to_mbps(rate));
         });
         Ok(())
@@ -229,10 +234,11 @@ impl DownloadStatisticsTab {
 
     fn update_connection_state(
         &self,
+        torrent_name: &str,
         peer_id: &[u8],
         state: PeerConnectionState,
     ) -> Result<(), DownloadStatisticsTabError> {
-        self.model.edit_state(peer_id, state);
+        self.model.edit_state(torrent_name, peer_id, state);
         Ok(())
     }
 
@@ -245,8 +251,12 @@ impl DownloadStatisticsTab {
         }
     }
 
-    fn close_connection(&self, peer_id: &[u8]) -> Result<(), DownloadStatisticsTabError> {
-        self.model.edit(peer_id, |item| {
+    fn close_connection(
+        &self,
+        torrent_name: &str,
+        peer_id: &[u8],
+    ) -> Result<(), DownloadStatisticsTabError> {
+        self.model.edit(torrent_name, peer_id, |item| {
             item.set_property("clientstate", &"Disconnected");
             item.set_property("peerstate", &"Disconnected");
             item.set_property("downloadrate", &0f32);
@@ -255,28 +265,50 @@ impl DownloadStatisticsTab {
         Ok(())
     }
 
+    // rebuilds every peer row from scratch, discarding the current ones
+    fn load_snapshot(&self, snapshot: &UISnapshot) -> Result<(), DownloadStatisticsTabError> {
+        self.model.clear();
+        for peer in &snapshot.peers {
+            self.add_peer(peer.statistics.clone())?;
+            let statistics = &peer.statistics;
+            self.model
+                .edit(&statistics.torrentname, &statistics.peerid, |item| {
+                    item.set_property("downloadedpieces", &peer.downloaded_pieces);
+                    item.set_property("downloadrate", &self.bytesps_to_mbps(peer.download_rate));
+                    item.set_property("uploadrate", &self.bytesps_to_mbps(peer.upload_rate));
+                    if !peer.is_connected {
+                        item.set_property("clientstate", &"Disconnected");
+                        item.set_property("peerstate", &"Disconnected");
+                    }
+                });
+        }
+        self.sort();
+        Ok(())
+    }
+
     pub fn update(&mut self, message: &UIMessage) -> Result<(), DownloadStatisticsTabError> {
         match message {
+            UIMessage::Snapshot(snapshot) => self.load_snapshot(snapshot)?,
             UIMessage::AddPeerStatistics(peer_statistics) => {
                 self.add_peer(peer_statistics.clone())?
             }
-            UIMessage::PieceDownloaded(_, peer_id) => {
-                self.update_downloaded_pieces(peer_id)?;
+            UIMessage::PieceDownloaded(torrent, peer_id) => {
+                self.update_downloaded_pieces(torrent, peer_id)?;
             }
-            UIMessage::UpdatePeerUploadRate(rate, peer_id) => {
-                self.update_upload_rate(*rate, peer_id)?;
+            UIMessage::UpdatePeerUploadRate(torrent, rate, peer_id) => {
+                self.update_upload_rate(torrent, *rate, peer_id)?;
             }
-            UIMessage::UpdatePeerDownloadRate(rate, peer_id) => {
-                self.update_download_rate(*rate, peer_id)?;
+            UIMessage::UpdatePeerDownloadRate(torrent, rate, peer_id) => {
+                self.update_download_rate(torrent, *rate, peer_id)?;
             }
-            UIMessage::UpdateDownloadedPiece(peer_id) => {
-                self.update_downloaded_pieces(peer_id)?;
+            UIMessage::UpdateDownloadedPiece(torrent, peer_id) => {
+                self.update_downloaded_pieces(torrent, peer_id)?;
             }
-            UIMessage::ClosedConnection(_, peer_id) => {
-                self.close_connection(peer_id)?;
+            UIMessage::ClosedConnection(torrent, peer_id) => {
+                self.close_connection(torrent, peer_id)?;
             }
-            UIMessage::UpdatePeerConnectionState(peer_id, peer_conn_state) => {
-                self.update_connection_state(peer_id, peer_conn_state.clone())?;
+            UIMessage::UpdatePeerConnectionState(torrent, peer_id, peer_conn_state) => {
+                self.update_connection_state(torrent, peer_id, peer_conn_state.clone())?;
             }
             _ => {}
         }
@@ -1,6 +1,6 @@
 use super::torrent_list_row::TorrentInformation;
 use super::torrent_model::Model;
-use super::UIMessage;
+use crate::event_bus::{UIMessage, UISnapshot};
 use crate::metainfo::Metainfo;
 use gtk::{self};
 use gtk::{
@@ -362,8 +362,27 @@ impl GeneralInformationTab {
         Ok(())
     }
 
+    // rebuilds every torrent row from scratch, discarding the current ones
+    fn load_snapshot(&self, snapshot: &UISnapshot) -> Result<(), GeneralInformationTabError> {
+        self.model.clear();
+        for torrent in &snapshot.torrents {
+            self.add_torrent(&torrent.metainfo)?;
+            self.model.edit(&torrent.metainfo.info.name, |item| {
+                let download_fraction: f32 = torrent.downloaded_pieces as f32
+                    / item.property::<u32>("totalpiececount") as f32;
+                item.set_property("peercount", &torrent.peer_count);
+                item.set_property("activeconnections", &torrent.active_connections);
+                item.set_property("downloadedpieces", &torrent.downloaded_pieces);
+                item.set_property("downloadfraction", &download_fraction);
+                item.set_property("downloadpercentage", &download_fraction * 100.0);
+            });
+        }
+        Ok(())
+    }
+
     pub fn update(&mut self, message: &UIMessage) -> Result<(), GeneralInformationTabError> {
         match message {
+            UIMessage::Snapshot(snapshot) => self.load_snapshot(snapshot)?,
             UIMessage::AddTorrent(metainfo) => self.add_torrent(metainfo)?,
             UIMessage::NewConnection(torrent) => self.add_connection_to_torrent(torrent)?,
             UIMessage::ClosedConnection(torrent, _) => {
@@ -3,15 +3,11 @@ mod download_statistics_model;
 mod download_statistics_row;
 mod download_statistics_tab;
 mod general_information_tab;
-mod messages;
 mod notebook;
 mod torrent_list_row;
 mod torrent_model;
-mod utils;
 
 pub use app::run_ui;
-pub use messages::{PeerStatistics, UIMessage, UIMessageSender};
 pub use notebook::{Notebook, NotebookError};
 pub use torrent_list_row::TorrentInformation;
 pub use torrent_model::Model;
-pub use utils::init_ui;
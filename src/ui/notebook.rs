@@ -1,6 +1,6 @@
 use super::download_statistics_tab::*;
 use super::general_information_tab::*;
-use super::UIMessage;
+use crate::event_bus::UIMessage;
 use gtk;
 use gtk::prelude::*;
 use gtk::Widget;
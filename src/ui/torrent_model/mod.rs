@@ -49,4 +49,11 @@ impl Model {
         // Emits a signal that 1 item was removed, 0 added at the position index
         self.items_changed(index, 1, 0);
     }
+
+    pub fn clear(&self) {
+        let len = self.imp().0.borrow().len();
+        for _ in 0..len {
+            self.remove(0);
+        }
+    }
 }
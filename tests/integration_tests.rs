@@ -1,9 +1,9 @@
 use bittorrent_rustico::client::*;
 use bittorrent_rustico::config::*;
 use bittorrent_rustico::constants::*;
+use bittorrent_rustico::event_bus::UIMessageSender;
 use bittorrent_rustico::metainfo::*;
 use bittorrent_rustico::peer::*;
-use bittorrent_rustico::ui::*;
 use sha1::{Digest, Sha1};
 use std::fs::File;
 use std::io::{Read, Write};
@@ -99,6 +99,7 @@ fn client_integration_test() {
     };
     let metainfo = Metainfo {
         announce: String::from("mock_url"),
+        announce_list: vec![],
         info_hash: vec![],
         info,
     };
@@ -142,6 +143,7 @@ fn get_metainfo(pieces: Vec<Vec<u8>>, info_hash: Vec<u8>) -> Metainfo {
 
     Metainfo {
         announce,
+        announce_list: vec![],
         info,
         info_hash,
     }
@@ -278,6 +280,12 @@ fn server_integration_test_ask_for_small_pieces() {
         log_path: "./log".to_string(),
         download_path: "./downloads".to_string(),
         persist_pieces: true,
+        candidate_ttl: Duration::from_secs(60),
+        candidate_max_failed_dials: 3,
+        dial_timeouts: DialTimeouts {
+            lan: Duration::from_secs(2),
+            wan: Duration::from_secs(100),
+        },
     };
 
     let client_info: ClientInfo = ClientInfo {
@@ -290,7 +298,7 @@ fn server_integration_test_ask_for_small_pieces() {
         peer_id,
         meta.clone(),
         port,
-        std::time::Duration::from_secs(2),
+        Duration::from_secs(2),
         "./downloads/test_server/pieces",
         TrackerService::new(client_info),
     );